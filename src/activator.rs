@@ -1,53 +1,261 @@
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ActivationContainer {
-    pub func: fn(f64) -> f64,
-    pub der: fn(f64) -> f64,
+    pub func: fn(f64, &[f64]) -> f64,
+    pub der: fn(f64, &[f64]) -> f64,
     pub name: String,
+    /// Parameters passed to `func` and `der` (leaky relu slope, elu alpha, ...)
+    pub params: Vec<f64>,
+    /// Activation over the whole layer (softmax), `func` and `der` are unused when set
+    pub vector: Option<VectorActivation>,
 }
 
-pub fn sigmoid() -> ActivationContainer {
-    fn func(input: f64) -> f64 {
-        1.0 / (1.0 + (-input).exp())
+#[derive(Debug, Clone, Copy)]
+pub struct VectorActivation {
+    pub func: fn(&[f64]) -> Vec<f64>,
+    /// Gradients w.r.t. the layer sums from the sums and the gradients w.r.t. the outputs
+    pub backward: fn(&[f64], &[f64]) -> Vec<f64>,
+}
+
+impl ActivationContainer {
+    /// Element-wise activation of `input`
+    pub fn activate(&self, input: f64) -> f64 {
+        (self.func)(input, &self.params)
+    }
+
+    /// Element-wise derivative at `input`
+    pub fn derivative(&self, input: f64) -> f64 {
+        (self.der)(input, &self.params)
+    }
+
+    /// Activate the weighted sums of a layer
+    pub fn apply(&self, sums: &[f64]) -> Vec<f64> {
+        match self.vector {
+            Some(v) => (v.func)(sums),
+            None => sums.iter().map(|s| self.activate(*s)).collect(),
+        }
     }
-    fn der(input: f64) -> f64 {
-        func(input) * (1.0 - func(input))
+
+    /// Gradients w.r.t. the layer sums given the gradients w.r.t. its outputs
+    pub fn backward(&self, sums: &[f64], grads: &[f64]) -> Vec<f64> {
+        match self.vector {
+            Some(v) => (v.backward)(sums, grads),
+            None => sums
+                .iter()
+                .zip(grads.iter())
+                .map(|(s, g)| self.derivative(*s) * g)
+                .collect(),
+        }
     }
+}
+
+/// Create an activation from its `name` and `params`, as written by `utills::io::save`.
+///
+/// Return `None` for an unknown name or a wrong number of parameters.
+pub fn from_name(name: &str, params: &[f64]) -> Option<ActivationContainer> {
+    let act = match (name, params) {
+        ("sigmoid", []) => sigmoid(),
+        ("relu", []) => relu(),
+        ("linear", []) => linear(),
+        ("softmax", []) => softmax(),
+        ("tanh", []) => tanh(),
+        ("leaky_relu", [slope]) => leaky_relu(*slope),
+        ("elu", [alpha]) => elu(*alpha),
+        ("selu", []) => selu(),
+        ("gelu", []) => gelu(),
+        ("softplus", []) => softplus(),
+        ("swish", [beta]) => swish(*beta),
+        _ => return None,
+    };
+    Some(act)
+}
+
+fn elementwise(
+    name: &str,
+    params: Vec<f64>,
+    func: fn(f64, &[f64]) -> f64,
+    der: fn(f64, &[f64]) -> f64,
+) -> ActivationContainer {
     ActivationContainer {
         func,
         der,
-        name: "sigmoid".to_string(),
+        name: name.to_string(),
+        params,
+        vector: None,
+    }
+}
+
+fn logistic(input: f64) -> f64 {
+    1.0 / (1.0 + (-input).exp())
+}
+
+pub fn sigmoid() -> ActivationContainer {
+    fn func(input: f64, _p: &[f64]) -> f64 {
+        logistic(input)
+    }
+    fn der(input: f64, _p: &[f64]) -> f64 {
+        logistic(input) * (1.0 - logistic(input))
     }
+    elementwise("sigmoid", vec![], func, der)
 }
 
 pub fn relu() -> ActivationContainer {
-    fn func(input: f64) -> f64 {
-        return f64::max(0.0, input);
+    fn func(input: f64, _p: &[f64]) -> f64 {
+        f64::max(0.0, input)
     }
-    fn der(input: f64) -> f64 {
+    fn der(input: f64, _p: &[f64]) -> f64 {
         if input > 0.0 {
-            return 1.0;
+            1.0
         } else {
-            return 0.0;
+            0.0
         }
     }
-    ActivationContainer {
-        func,
-        der,
-        name: "relu".to_string(),
-    }
+    elementwise("relu", vec![], func, der)
 }
 
 pub fn linear() -> ActivationContainer {
-    fn func(input: f64) -> f64 {
+    fn func(input: f64, _p: &[f64]) -> f64 {
         input
     }
-    fn der(_input: f64) -> f64 {
+    fn der(_input: f64, _p: &[f64]) -> f64 {
         1.0
     }
+    elementwise("linear", vec![], func, der)
+}
+
+pub fn tanh() -> ActivationContainer {
+    fn func(input: f64, _p: &[f64]) -> f64 {
+        input.tanh()
+    }
+    fn der(input: f64, _p: &[f64]) -> f64 {
+        1.0 - input.tanh().powi(2)
+    }
+    elementwise("tanh", vec![], func, der)
+}
+
+/// ReLU with `slope` for negative inputs instead of 0
+pub fn leaky_relu(slope: f64) -> ActivationContainer {
+    fn func(input: f64, p: &[f64]) -> f64 {
+        if input > 0.0 {
+            input
+        } else {
+            p[0] * input
+        }
+    }
+    fn der(input: f64, p: &[f64]) -> f64 {
+        if input > 0.0 {
+            1.0
+        } else {
+            p[0]
+        }
+    }
+    elementwise("leaky_relu", vec![slope], func, der)
+}
+
+/// Exponential linear unit, saturates to `-alpha` for negative inputs
+pub fn elu(alpha: f64) -> ActivationContainer {
+    fn func(input: f64, p: &[f64]) -> f64 {
+        if input > 0.0 {
+            input
+        } else {
+            p[0] * (input.exp() - 1.0)
+        }
+    }
+    fn der(input: f64, p: &[f64]) -> f64 {
+        if input > 0.0 {
+            1.0
+        } else {
+            p[0] * input.exp()
+        }
+    }
+    elementwise("elu", vec![alpha], func, der)
+}
+
+/// Scaled ELU with the self-normalizing constants
+pub fn selu() -> ActivationContainer {
+    const LAMBDA: f64 = 1.050_700_987_355_480_5;
+    const ALPHA: f64 = 1.673_263_242_354_377_2;
+    fn func(input: f64, _p: &[f64]) -> f64 {
+        if input > 0.0 {
+            LAMBDA * input
+        } else {
+            LAMBDA * ALPHA * (input.exp() - 1.0)
+        }
+    }
+    fn der(input: f64, _p: &[f64]) -> f64 {
+        if input > 0.0 {
+            LAMBDA
+        } else {
+            LAMBDA * ALPHA * input.exp()
+        }
+    }
+    elementwise("selu", vec![], func, der)
+}
+
+/// Gaussian error linear unit (tanh approximation)
+pub fn gelu() -> ActivationContainer {
+    // sqrt(2 / pi)
+    const C: f64 = 0.797_884_560_802_865_4;
+    fn func(input: f64, _p: &[f64]) -> f64 {
+        0.5 * input * (1.0 + (C * (input + 0.044715 * input.powi(3))).tanh())
+    }
+    fn der(input: f64, _p: &[f64]) -> f64 {
+        let t = (C * (input + 0.044715 * input.powi(3))).tanh();
+        0.5 * (1.0 + t) + 0.5 * input * (1.0 - t * t) * C * (1.0 + 3.0 * 0.044715 * input.powi(2))
+    }
+    elementwise("gelu", vec![], func, der)
+}
+
+/// Smooth ReLU, ln(1 + e^x)
+pub fn softplus() -> ActivationContainer {
+    fn func(input: f64, _p: &[f64]) -> f64 {
+        // stable for large |x|
+        input.max(0.0) + (1.0 + (-input.abs()).exp()).ln()
+    }
+    fn der(input: f64, _p: &[f64]) -> f64 {
+        logistic(input)
+    }
+    elementwise("softplus", vec![], func, der)
+}
+
+/// x * sigmoid(beta * x), `beta` = 1 is SiLU
+pub fn swish(beta: f64) -> ActivationContainer {
+    fn func(input: f64, p: &[f64]) -> f64 {
+        input * logistic(p[0] * input)
+    }
+    fn der(input: f64, p: &[f64]) -> f64 {
+        let s = logistic(p[0] * input);
+        s + p[0] * input * s * (1.0 - s)
+    }
+    elementwise("swish", vec![beta], func, der)
+}
+
+/// Softmax over the whole layer, pair it with `Loss::cce` for multi-class outputs.
+pub fn softmax() -> ActivationContainer {
+    fn func(sums: &[f64]) -> Vec<f64> {
+        // shift by max for numerical stability
+        let max = sums.iter().fold(f64::MIN, |m, &v| m.max(v));
+        let exps: Vec<f64> = sums.iter().map(|s| (s - max).exp()).collect();
+        let total: f64 = exps.iter().sum();
+        exps.iter().map(|e| e / total).collect()
+    }
+    fn backward(sums: &[f64], grads: &[f64]) -> Vec<f64> {
+        // Jacobian-vector product: y_j * (g_j - sum_k g_k * y_k)
+        let y = func(sums);
+        let dot: f64 = y.iter().zip(grads.iter()).map(|(y_k, g_k)| y_k * g_k).sum();
+        y.iter()
+            .zip(grads.iter())
+            .map(|(y_j, g_j)| y_j * (g_j - dot))
+            .collect()
+    }
+    fn unused(_input: f64, _p: &[f64]) -> f64 {
+        panic!("softmax is not an element-wise activation")
+    }
     ActivationContainer {
-        func,
-        der,
-        name: "linear".to_string(),
+        func: unused,
+        der: unused,
+        name: "softmax".to_string(),
+        params: vec![],
+        vector: Some(VectorActivation { func, backward }),
     }
 }
 
@@ -59,21 +267,113 @@ mod tests {
     fn test_sigmoid() {
         let act = sigmoid();
 
-        assert_eq!((act.func)(1.0), 0.7310585786300048792512);
-        assert_eq!((act.func)(-1.0), 0.2689414213699951207488);
-        assert_eq!((act.func)(0.0), 0.5);
-        assert_eq!((act.der)(1.0), 0.1966119332414818525374);
-        assert_eq!((act.der)(-1.0), 0.1966119332414818525374);
-        assert_eq!((act.der)(0.0), 0.25);
+        assert_eq!(act.activate(1.0), 0.7310585786300048792512);
+        assert_eq!(act.activate(-1.0), 0.2689414213699951207488);
+        assert_eq!(act.activate(0.0), 0.5);
+        assert_eq!(act.derivative(1.0), 0.1966119332414818525374);
+        assert_eq!(act.derivative(-1.0), 0.1966119332414818525374);
+        assert_eq!(act.derivative(0.0), 0.25);
     }
 
     #[test]
     fn test_relu() {
         let act = relu();
 
-        assert_eq!((act.func)(-1.0), 0.0);
-        assert_eq!((act.func)(20.0), 20.0);
-        assert_eq!((act.der)(-1.0), 0.0);
-        assert_eq!((act.der)(20.0), 1.0);
+        assert_eq!(act.activate(-1.0), 0.0);
+        assert_eq!(act.activate(20.0), 20.0);
+        assert_eq!(act.derivative(-1.0), 0.0);
+        assert_eq!(act.derivative(20.0), 1.0);
+    }
+
+    #[test]
+    fn test_parameterized() {
+        let act = leaky_relu(0.1);
+        assert_eq!(act.activate(-2.0), -0.2);
+        assert_eq!(act.activate(3.0), 3.0);
+        assert_eq!(act.derivative(-2.0), 0.1);
+
+        let act = elu(2.0);
+        assert_eq!(act.activate(0.0), 0.0);
+        assert!((act.activate(-100.0) + 2.0).abs() < 1e-12);
+
+        assert_eq!(tanh().activate(0.0), 0.0);
+        assert_eq!(gelu().activate(0.0), 0.0);
+        assert!((softplus().activate(0.0) - 2f64.ln()).abs() < 1e-12);
+        assert_eq!(softplus().activate(1000.0), 1000.0);
+        assert_eq!(swish(1.0).activate(0.0), 0.0);
+        assert!((selu().activate(1.0) - 1.0507009873554805).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_derivatives() {
+        let acts = vec![
+            sigmoid(),
+            relu(),
+            linear(),
+            tanh(),
+            leaky_relu(0.01),
+            elu(1.0),
+            selu(),
+            gelu(),
+            softplus(),
+            swish(1.5),
+        ];
+        let h = 1e-6;
+        for act in acts.iter() {
+            // avoid the kink of relu-like functions at 0
+            for x in [-2.5, -0.7, 0.3, 1.9] {
+                let numeric = (act.activate(x + h) - act.activate(x - h)) / (2.0 * h);
+                assert!(
+                    (act.derivative(x) - numeric).abs() < 1e-6,
+                    "{} derivative is wrong at {}",
+                    act.name,
+                    x
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_name() {
+        for act in [sigmoid(), softmax(), leaky_relu(0.2), elu(0.5), swish(2.0)] {
+            let loaded = from_name(&act.name, &act.params).unwrap();
+            assert_eq!(loaded.name, act.name);
+            assert_eq!(loaded.params, act.params);
+        }
+        assert!(from_name("unknown", &[]).is_none());
+        assert!(from_name("leaky_relu", &[]).is_none());
+        assert!(from_name("relu", &[1.0]).is_none());
+    }
+
+    #[test]
+    fn test_softmax() {
+        let act = softmax();
+        let y = act.apply(&[1.0, 2.0, 3.0]);
+        assert!((y.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert!(y[0] < y[1] && y[1] < y[2]);
+        // large sums do not overflow
+        let y = act.apply(&[1000.0, 1000.0]);
+        assert_eq!(y, vec![0.5, 0.5]);
+
+        // compare with finite differences of sum_j g_j * y_j
+        let sums = [0.5, -1.0, 2.0];
+        let grads = [1.0, -2.0, 0.5];
+        let analytic = act.backward(&sums, &grads);
+        let h = 1e-6;
+        for j in 0..sums.len() {
+            let mut plus = sums;
+            let mut minus = sums;
+            plus[j] += h;
+            minus[j] -= h;
+            let f = |s: &[f64]| -> f64 {
+                act.apply(s)
+                    .iter()
+                    .zip(grads.iter())
+                    .map(|(y, g)| y * g)
+                    .sum()
+            };
+            let numeric = (f(&plus) - f(&minus)) / (2.0 * h);
+            assert!((analytic[j] - numeric).abs() < 1e-6);
+        }
     }
 }
@@ -0,0 +1,272 @@
+//! Recombination of two parent chromosomes into two children.
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::mlp::Net;
+
+pub trait Crossover {
+    fn cross(&self, a: &[f64], b: &[f64], rng: &mut StdRng) -> (Vec<f64>, Vec<f64>);
+}
+
+/// Every gene comes from either parent with the same probability, the second child
+/// gets the other one
+pub struct Uniform;
+
+impl Crossover for Uniform {
+    fn cross(&self, a: &[f64], b: &[f64], rng: &mut StdRng) -> (Vec<f64>, Vec<f64>) {
+        a.iter()
+            .zip(b.iter())
+            .map(|(x, y)| {
+                if rng.gen_bool(0.5) {
+                    (*x, *y)
+                } else {
+                    (*y, *x)
+                }
+            })
+            .unzip()
+    }
+}
+
+/// BLX-α: every child gene is drawn uniformly from the parents' interval widened by
+/// `alpha` times its length on both sides
+pub struct Blend {
+    pub alpha: f64,
+}
+
+impl Blend {
+    pub fn new(alpha: f64) -> Blend {
+        Blend { alpha }
+    }
+}
+
+impl Crossover for Blend {
+    fn cross(&self, a: &[f64], b: &[f64], rng: &mut StdRng) -> (Vec<f64>, Vec<f64>) {
+        a.iter()
+            .zip(b.iter())
+            .map(|(x, y)| {
+                let (lo, hi) = (x.min(*y), x.max(*y));
+                let d = self.alpha * (hi - lo);
+                if d == 0.0 {
+                    (lo, lo)
+                } else {
+                    (
+                        rng.gen_range(lo - d..=hi + d),
+                        rng.gen_range(lo - d..=hi + d),
+                    )
+                }
+            })
+            .unzip()
+    }
+}
+
+/// Simulated binary crossover: children spread around the parents like one-point
+/// crossover of binary strings, closer to them for a larger distribution index `eta`
+pub struct Sbx {
+    pub eta: f64,
+}
+
+impl Sbx {
+    pub fn new(eta: f64) -> Sbx {
+        Sbx { eta }
+    }
+}
+
+impl Crossover for Sbx {
+    fn cross(&self, a: &[f64], b: &[f64], rng: &mut StdRng) -> (Vec<f64>, Vec<f64>) {
+        a.iter()
+            .zip(b.iter())
+            .map(|(x, y)| {
+                let u: f64 = rng.gen();
+                let beta = if u <= 0.5 {
+                    (2.0 * u).powf(1.0 / (self.eta + 1.0))
+                } else {
+                    (1.0 / (2.0 * (1.0 - u))).powf(1.0 / (self.eta + 1.0))
+                };
+                (
+                    0.5 * ((1.0 + beta) * x + (1.0 - beta) * y),
+                    0.5 * ((1.0 - beta) * x + (1.0 + beta) * y),
+                )
+            })
+            .unzip()
+    }
+}
+
+/// Whole arithmetic crossover: `w * a + (1 - w) * b` and `(1 - w) * a + w * b`, with a
+/// new uniform `w` for every pair when `weight` is `None`
+pub struct Arithmetic {
+    pub weight: Option<f64>,
+}
+
+impl Crossover for Arithmetic {
+    fn cross(&self, a: &[f64], b: &[f64], rng: &mut StdRng) -> (Vec<f64>, Vec<f64>) {
+        let w = self.weight.unwrap_or_else(|| rng.gen());
+        a.iter()
+            .zip(b.iter())
+            .map(|(x, y)| (w * x + (1.0 - w) * y, (1.0 - w) * x + w * y))
+            .unzip()
+    }
+}
+
+/// Swap the genes between `cuts` (sorted, exclusive ends) every other segment,
+/// starting with the second
+fn swap_segments(a: &[f64], b: &[f64], cuts: &[usize]) -> (Vec<f64>, Vec<f64>) {
+    let (mut c1, mut c2) = (a.to_vec(), b.to_vec());
+    let mut bounds = vec![0];
+    bounds.extend_from_slice(cuts);
+    bounds.push(a.len());
+    for (i, s) in bounds.windows(2).enumerate() {
+        if i % 2 == 1 {
+            c1[s[0]..s[1]].copy_from_slice(&b[s[0]..s[1]]);
+            c2[s[0]..s[1]].copy_from_slice(&a[s[0]..s[1]]);
+        }
+    }
+    (c1, c2)
+}
+
+/// Genes after a random cut point come from the other parent
+pub struct OnePoint;
+
+impl Crossover for OnePoint {
+    fn cross(&self, a: &[f64], b: &[f64], rng: &mut StdRng) -> (Vec<f64>, Vec<f64>) {
+        if a.len() < 2 {
+            return (a.to_vec(), b.to_vec());
+        }
+        swap_segments(a, b, &[rng.gen_range(1..a.len())])
+    }
+}
+
+/// Genes between two random cut points come from the other parent
+pub struct TwoPoint;
+
+impl Crossover for TwoPoint {
+    fn cross(&self, a: &[f64], b: &[f64], rng: &mut StdRng) -> (Vec<f64>, Vec<f64>) {
+        if a.len() < 3 {
+            return OnePoint.cross(a, b, rng);
+        }
+        let mut cuts = rand::seq::index::sample(rng, a.len() - 1, 2).into_vec();
+        cuts.iter_mut().for_each(|c| *c += 1);
+        cuts.sort_unstable();
+        swap_segments(a, b, &cuts)
+    }
+}
+
+/// Uniform crossover of whole neurons of the net the chromosomes come from: the
+/// incoming weights (a row of `Layer::w`) and the bias of a neuron are swapped
+/// together, so functional units are not torn apart
+pub struct Neuron {
+    /// Gene indices of every neuron
+    pub neurons: Vec<Vec<usize>>,
+}
+
+impl Neuron {
+    pub fn new(net: &Net) -> Neuron {
+        let mut neurons: Vec<Vec<usize>> = vec![];
+        let mut offset = 0;
+        for l in net.layers.iter() {
+            let (outputs, inputs) = (l.w.rows(), l.w.cols());
+            for j in 0..outputs {
+                let mut genes: Vec<usize> =
+                    (offset + j * inputs..offset + (j + 1) * inputs).collect();
+                genes.push(offset + outputs * inputs + j);
+                neurons.push(genes);
+            }
+            offset += outputs * inputs + outputs;
+        }
+        Neuron { neurons }
+    }
+}
+
+impl Crossover for Neuron {
+    fn cross(&self, a: &[f64], b: &[f64], rng: &mut StdRng) -> (Vec<f64>, Vec<f64>) {
+        let (mut c1, mut c2) = (a.to_vec(), b.to_vec());
+        for genes in self.neurons.iter() {
+            if rng.gen_bool(0.5) {
+                for &g in genes {
+                    c1[g] = b[g];
+                    c2[g] = a[g];
+                }
+            }
+        }
+        (c1, c2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activator;
+    use crate::mlp::Layer;
+    use rand::SeedableRng;
+
+    fn parents() -> (Vec<f64>, Vec<f64>) {
+        ((0..8).map(|i| i as f64).collect(), vec![-1.0; 8])
+    }
+
+    #[test]
+    fn test_blend_and_sbx() {
+        let (a, b) = parents();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let (c1, c2) = Blend::new(0.5).cross(&a, &b, &mut rng);
+            for i in 0..8 {
+                let d = 0.5 * (a[i] - b[i]);
+                assert!(c1[i] >= b[i] - d && c1[i] <= a[i] + d);
+                assert!(c2[i] >= b[i] - d && c2[i] <= a[i] + d);
+            }
+            // sbx children are symmetric around the parents' mean
+            let (c1, c2) = Sbx::new(2.0).cross(&a, &b, &mut rng);
+            for i in 0..8 {
+                assert!((c1[i] + c2[i] - a[i] - b[i]).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let (a, b) = parents();
+        let (c1, c2) =
+            Arithmetic { weight: Some(0.25) }.cross(&a, &b, &mut StdRng::seed_from_u64(0));
+        assert_eq!(c1[4], 0.25 * 4.0 - 0.75);
+        assert_eq!(c2[4], 0.75 * 4.0 - 0.25);
+    }
+
+    #[test]
+    fn test_point_crossovers() {
+        let (a, b) = parents();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..20 {
+            let (c1, c2) = OnePoint.cross(&a, &b, &mut rng);
+            let cut = c1.iter().position(|g| *g == -1.0).unwrap();
+            assert!(cut > 0);
+            assert_eq!(c1[..cut], a[..cut]);
+            assert!(c1[cut..].iter().all(|g| *g == -1.0));
+            assert_eq!(c2[cut..], a[cut..]);
+
+            // a single segment of c1 comes from b
+            let (c1, _) = TwoPoint.cross(&a, &b, &mut rng);
+            let from_b: Vec<usize> = (0..8).filter(|&i| c1[i] == -1.0).collect();
+            assert!(!from_b.is_empty() && from_b[0] > 0 && *from_b.last().unwrap() < 7);
+            assert_eq!(from_b.last().unwrap() - from_b[0] + 1, from_b.len());
+        }
+    }
+
+    #[test]
+    fn test_neuron() {
+        let net = Net::from_layers(vec![
+            Layer::new(2, 2, 1.0, activator::sigmoid()),
+            Layer::new(2, 1, 1.0, activator::sigmoid()),
+        ]);
+        let n = Neuron::new(&net);
+        assert_eq!(n.neurons, vec![vec![0, 1, 4], vec![2, 3, 5], vec![6, 7, 8]]);
+
+        let (a, b): (Vec<f64>, Vec<f64>) = ((0..9).map(|i| i as f64).collect(), vec![-1.0; 9]);
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..20 {
+            let (c1, _) = n.cross(&a, &b, &mut rng);
+            for genes in n.neurons.iter() {
+                let from_b = genes.iter().filter(|&&g| c1[g] == -1.0).count();
+                assert!(from_b == 0 || from_b == genes.len());
+            }
+        }
+    }
+}
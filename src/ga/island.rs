@@ -0,0 +1,263 @@
+//! Island model: genetic algorithms evolving side by side and exchanging their best
+//! individuals.
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{assign_ind, cmp_fitness, GenStats, GeneticAlgorithm, Individual};
+use crate::mlp::Net;
+
+/// Where the migrants of an island go
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Topology {
+    /// To the next island, from the last one to the first
+    Ring,
+    /// To every other island
+    FullyConnected,
+    /// To another island drawn at every migration
+    Random,
+}
+
+impl Topology {
+    /// Islands receiving the migrants of island `from` out of `n`
+    pub fn destinations(&self, from: usize, n: usize, rng: &mut StdRng) -> Vec<usize> {
+        if n < 2 {
+            return vec![];
+        }
+        match self {
+            Topology::Ring => vec![(from + 1) % n],
+            Topology::FullyConnected => (0..n).filter(|&i| i != from).collect(),
+            Topology::Random => {
+                // any island but `from`
+                let i = rng.gen_range(0..n - 1);
+                vec![if i >= from { i + 1 } else { i }]
+            }
+        }
+    }
+}
+
+/// Islands evolving in lockstep, each with its own `GeneticAlgorithm` settings
+/// (population, operators, seed). Every `interval` generations copies of the
+/// `migrants` fittest individuals of each island are sent along `topology`, they
+/// replace the least fit individuals they beat before the next generation is bred.
+///
+/// `init` then `step` until `done`, or `run` to do all of it.
+pub struct IslandModel {
+    pub islands: Vec<GeneticAlgorithm>,
+    pub topology: Topology,
+    /// Generations between two migrations
+    pub interval: usize,
+    pub migrants: usize,
+    rng: StdRng,
+    fitness_vals: Vec<f64>,
+    /// Statistics over every island
+    pub stats: Vec<GenStats>,
+}
+
+impl IslandModel {
+    /// Ring topology migrating the best individual every 10 generations, `seed` only
+    /// draws the `Random` topology
+    pub fn new(islands: Vec<GeneticAlgorithm>, seed: u64) -> IslandModel {
+        if islands.is_empty() {
+            panic!("there must be at least one island");
+        }
+        IslandModel {
+            islands,
+            topology: Topology::Ring,
+            interval: 10,
+            migrants: 1,
+            rng: StdRng::seed_from_u64(seed),
+            fitness_vals: vec![],
+            stats: vec![],
+        }
+    }
+
+    pub fn topology(mut self, topology: Topology) -> IslandModel {
+        self.topology = topology;
+        self
+    }
+
+    pub fn interval(mut self, generations: usize) -> IslandModel {
+        if generations == 0 {
+            panic!("interval must be at least 1");
+        }
+        self.interval = generations;
+        self
+    }
+
+    pub fn migrants(mut self, migrants: usize) -> IslandModel {
+        self.migrants = migrants;
+        self
+    }
+
+    /// New random population on every island, the statistics are cleared
+    pub fn init(&mut self, net: &Net) {
+        for island in self.islands.iter_mut() {
+            island.init(net);
+        }
+        self.fitness_vals.clear();
+        self.stats.clear();
+    }
+
+    /// Evaluate every island with `fitness` on clones of `net`, migrate, then breed
+    /// their next generation. `net` is left with the best individual seen so far.
+    pub fn step(&mut self, net: &mut Net, fitness: &(dyn Fn(&mut Net) -> f64 + Sync)) -> &GenStats {
+        for island in self.islands.iter_mut() {
+            island.evaluate(net, fitness);
+        }
+        self.fitness_vals = self
+            .islands
+            .iter()
+            .flat_map(|i| i.fitness_vals.iter().cloned())
+            .collect();
+        self.stats
+            .push(GenStats::new(self.stats.len(), &self.fitness_vals));
+
+        if self.stats.len() % self.interval == 0 {
+            self.migrate();
+        }
+        for island in self.islands.iter_mut() {
+            island.pop = island.breed();
+        }
+        assign_ind(net, self.best().unwrap());
+        self.stats.last().unwrap()
+    }
+
+    /// Send the fittest individuals of the evaluated populations along `topology`
+    fn migrate(&mut self) {
+        let n = self.islands.len();
+        let mut incoming: Vec<Vec<Individual>> = vec![vec![]; n];
+        for (from, island) in self.islands.iter().enumerate() {
+            let mut ranked: Vec<&Individual> = island.pop.iter().collect();
+            ranked.sort_by(|a, b| cmp_fitness(b.fitness, a.fitness));
+            for to in self.topology.destinations(from, n, &mut self.rng) {
+                incoming[to].extend(ranked.iter().take(self.migrants).map(|p| (*p).clone()));
+            }
+        }
+        for (island, migrants) in self.islands.iter_mut().zip(incoming) {
+            let size = island.pop.len();
+            island.pop.extend(migrants);
+            // stable, a migrant does not replace a resident as fit as itself
+            island.pop.sort_by(|a, b| cmp_fitness(b.fitness, a.fitness));
+            island.pop.truncate(size);
+        }
+    }
+
+    /// Whether every island meets one of its termination criteria
+    pub fn done(&self) -> bool {
+        self.islands.iter().all(|i| i.done())
+    }
+
+    /// Evolve `net` from new populations until `done`, `net` is left with the best
+    /// individual which is returned
+    pub fn run(&mut self, net: &mut Net, fitness: &(dyn Fn(&mut Net) -> f64 + Sync)) -> Individual {
+        self.init(net);
+        while !self.done() {
+            self.step(net, fitness);
+        }
+        self.best().unwrap().clone()
+    }
+
+    /// Best individual of every island since `init`
+    pub fn best(&self) -> Option<&Individual> {
+        self.islands.iter().filter_map(|i| i.best()).reduce(|b, i| {
+            if cmp_fitness(i.fitness, b.fitness).is_gt() {
+                i
+            } else {
+                b
+            }
+        })
+    }
+
+    /// Fitness of every individual of the last evaluated generation, island after island
+    pub fn fitness(&self) -> &[f64] {
+        &self.fitness_vals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        activator,
+        ga::{crossover, mutation},
+        mlp::Layer,
+    };
+
+    fn small_net() -> Net {
+        Net::from_layers(vec![Layer::new(2, 2, 1.0, activator::sigmoid())])
+    }
+
+    /// Highest when every parameter is 0.5
+    fn fitness(net: &mut Net) -> f64 {
+        -net.get_params()
+            .iter()
+            .map(|p| (p - 0.5).powi(2))
+            .sum::<f64>()
+    }
+
+    #[test]
+    fn test_destinations() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(Topology::Ring.destinations(3, 4, &mut rng), vec![0]);
+        assert_eq!(
+            Topology::FullyConnected.destinations(1, 4, &mut rng),
+            vec![0, 2, 3]
+        );
+        for _ in 0..20 {
+            let to = Topology::Random.destinations(2, 4, &mut rng);
+            assert_eq!(to.len(), 1);
+            assert_ne!(to[0], 2);
+        }
+        assert!(Topology::Ring.destinations(0, 1, &mut rng).is_empty());
+    }
+
+    #[test]
+    fn test_migrate() {
+        let island = |fitness: [f64; 3]| {
+            let mut ga = GeneticAlgorithm::new(3, 0);
+            ga.pop = fitness
+                .iter()
+                .map(|&f| {
+                    let mut ind = Individual::new(vec![f]);
+                    ind.set_fitness(f);
+                    ind
+                })
+                .collect();
+            ga
+        };
+        let mut model =
+            IslandModel::new(vec![island([1.0, 2.0, 3.0]), island([4.0, 0.0, 5.0])], 0).migrants(2);
+        model.migrate();
+        let fitness =
+            |i: usize| -> Vec<f64> { model.islands[i].pop.iter().map(|p| p.fitness).collect() };
+        assert_eq!(fitness(0), vec![5.0, 4.0, 3.0]);
+        assert_eq!(fitness(1), vec![5.0, 4.0, 3.0]);
+    }
+
+    #[test]
+    fn test_island_run() {
+        let run = || {
+            let islands = vec![
+                GeneticAlgorithm::new(10, 1).max_gen(40),
+                GeneticAlgorithm::new(10, 2)
+                    .max_gen(40)
+                    .crossover(Box::new(crossover::Blend::new(0.5))),
+                GeneticAlgorithm::new(10, 3)
+                    .max_gen(40)
+                    .mutation(Box::new(mutation::Gaussian::new(0.2, 0.1))),
+            ];
+            let mut model = IslandModel::new(islands, 4)
+                .topology(Topology::Random)
+                .interval(5);
+            let mut net = small_net();
+            let best = model.run(&mut net, &fitness);
+            assert_eq!(net.get_params(), best.chromosome);
+            (best.chromosome, model.stats)
+        };
+        let (best, stats) = run();
+        assert_eq!(stats.len(), 40);
+        // every island keeps its elite
+        assert!(stats.windows(2).all(|s| s[1].best >= s[0].best));
+        assert!(stats[39].best > stats[0].best);
+        assert_eq!(run(), (best, stats));
+    }
+}
@@ -1,14 +1,25 @@
 //! Genictic Algorithm Utility
+pub mod crossover;
+pub mod island;
+pub mod mutation;
+pub mod nsga;
 pub mod selection;
-use rand::{distributions::Uniform, prelude::Distribution, seq::SliceRandom, Rng};
-use std::f64::consts::E;
+use rand::rngs::StdRng;
+use rand::{distributions::Uniform, prelude::Distribution, seq::SliceRandom, Rng, SeedableRng};
+use std::cmp::Ordering;
 
 use crate::mlp::Net;
+use crate::utills::parallel;
+use crossover::Crossover;
+use mutation::Mutation;
+use selection::{Selection, Tournament};
 
 #[derive(Clone)]
 pub struct Individual {
     pub chromosome: Vec<f64>,
     pub fitness: f64,
+    /// Step size of every gene for `mutation::SelfAdaptive`, empty until mutated by it
+    pub sigma: Vec<f64>,
 }
 
 impl Individual {
@@ -16,6 +27,7 @@ impl Individual {
         Individual {
             chromosome,
             fitness: 0.0,
+            sigma: vec![],
         }
     }
 
@@ -24,6 +36,12 @@ impl Individual {
     }
 }
 
+/// Order of two fitness values, NaN is worse than any other
+fn cmp_fitness(a: f64, b: f64) -> Ordering {
+    let key = |f: f64| if f.is_nan() { f64::NEG_INFINITY } else { f };
+    key(a).total_cmp(&key(b))
+}
+
 /// return result of mating of individual in the pool
 pub fn mating(pop: &Vec<Individual>) -> Vec<Individual> {
     let mut rand = rand::thread_rng();
@@ -64,43 +82,43 @@ pub fn mutate(pop: &Vec<Individual>, amount: usize, p_m: f64) -> Vec<Individual>
     new_pop
 }
 
-/// non-uniform strong mutation
+/// non-uniform strong mutation of `amount` sampled individuals, the mutation
+/// probability decreases linearly to 0 at `max_gen`
 pub fn mutate_nonuni(
-    pop: &Vec<Individual>,
+    pop: &[Individual],
     amount: usize,
     p_m: f64,
     curr_gen: usize,
+    max_gen: usize,
 ) -> Vec<Individual> {
-    let mut new_pop: Vec<Individual> = vec![];
     let mut rand = rand::thread_rng();
-    let beta = 1.0;
-    for i in 0..amount {
-        let mut ind_clone = pop[i].clone();
-        for j in 0..pop[i].chromosome.len() {
-            let between = Uniform::from(0.0..=1.0);
-            if between.sample(&mut rand) < (p_m * E.powf(-beta * curr_gen as f64)) {
-                let change = 2f64 * rand::random::<f64>() - 1f64;
-                ind_clone.chromosome[j] += change;
+    let p = p_m * mutation::Anneal::Linear.factor(curr_gen, max_gen);
+    pop.choose_multiple(&mut rand::thread_rng(), amount)
+        .map(|ind| {
+            let mut ind_clone = ind.clone();
+            for gene in ind_clone.chromosome.iter_mut() {
+                let between = Uniform::from(0.0..=1.0);
+                if between.sample(&mut rand) < p {
+                    let change = 2f64 * rand::random::<f64>() - 1f64;
+                    *gene += change;
+                }
             }
-        }
-        new_pop.push(ind_clone);
-    }
-    new_pop
+            ind_clone
+        })
+        .collect()
 }
 
 /// Create inital population of MLP from layers
 ///
 /// return: population
-pub fn init_pop(net: &Net, amount: u32) -> Vec<Individual> {
+pub fn init_pop(net: &Net, amount: u32, rng: &mut StdRng) -> Vec<Individual> {
     let mut pop: Vec<Individual> = vec![];
     for _ in 0..(amount) {
         let mut chromosome: Vec<f64> = vec![];
         for l in &net.layers {
-            for output in &l.w {
-                for _ in output {
-                    // new random weight in range [-1, 1]
-                    chromosome.push(2f64 * rand::random::<f64>() - 1f64);
-                }
+            for _ in l.w.as_slice() {
+                // new random weight in range [-1, 1]
+                chromosome.push(rng.gen_range(-1.0..=1.0));
             }
             for bias in &l.b {
                 chromosome.push(*bias);
@@ -113,23 +131,285 @@ pub fn init_pop(net: &Net, amount: u32) -> Vec<Individual> {
 
 /// assign individual weigth to net
 pub fn assign_ind(net: &mut Net, individual: &Individual) {
-    if net.parameters != individual.chromosome.len() as u64 {
-        panic!["The neural network parameters size is not equal to individual size"];
+    net.set_params(&individual.chromosome);
+}
+
+/// Unmutated children of two parents, crossed with probability `crossover_rate` else
+/// copies of them
+fn offspring(
+    parents: [&Individual; 2],
+    crossover: &dyn Crossover,
+    crossover_rate: f64,
+    rng: &mut StdRng,
+) -> [Individual; 2] {
+    let [p0, p1] = parents;
+    let crossed = rng.gen_bool(crossover_rate);
+    let (a, b) = if crossed {
+        crossover.cross(&p0.chromosome, &p1.chromosome, rng)
+    } else {
+        (p0.chromosome.clone(), p1.chromosome.clone())
+    };
+    // crossed children share the mean step sizes of their parents
+    let sigma = if crossed && p0.sigma.len() == p1.sigma.len() {
+        let mean: Vec<f64> = p0
+            .sigma
+            .iter()
+            .zip(p1.sigma.iter())
+            .map(|(x, y)| (x + y) / 2.0)
+            .collect();
+        [mean.clone(), mean]
+    } else {
+        [p0.sigma.clone(), p1.sigma.clone()]
+    };
+    let [s0, s1] = sigma;
+    let mut children = [Individual::new(a), Individual::new(b)];
+    children[0].sigma = s0;
+    children[1].sigma = s1;
+    children
+}
+
+/// Fitness of one generation
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenStats {
+    pub generation: usize,
+    pub best: f64,
+    pub mean: f64,
+    pub worst: f64,
+    pub std: f64,
+}
+
+impl GenStats {
+    fn new(generation: usize, fitness: &[f64]) -> GenStats {
+        let n = fitness.len() as f64;
+        let mean = fitness.iter().sum::<f64>() / n;
+        GenStats {
+            generation,
+            best: fitness.iter().cloned().fold(f64::MIN, f64::max),
+            mean,
+            worst: fitness.iter().cloned().fold(f64::MAX, f64::min),
+            std: (fitness.iter().map(|f| (f - mean).powi(2)).sum::<f64>() / n).sqrt(),
+        }
     }
-    let mut idx: usize = 0;
+}
 
-    for l in &mut net.layers {
-        l.w.iter_mut().for_each(|w_j| {
-            w_j.iter_mut().for_each(|w_ji| {
-                *w_ji = individual.chromosome[idx];
-                idx += 1;
-            })
-        });
+/// Generational GA maximizing a fitness of the net parameters. Every generation the
+/// `elitism` fittest individuals are kept as is and the rest of the population is
+/// made of mutated children of selected parents.
+///
+/// `init` then `step` until `done`, or `run` to do all of it.
+pub struct GeneticAlgorithm {
+    pub pop_size: usize,
+    pub elitism: usize,
+    /// Probability that a pair of parents is crossed, else the children are copies
+    pub crossover_rate: f64,
+    pub max_gen: usize,
+    /// Stop once the best fitness reaches this value
+    pub target: Option<f64>,
+    /// Stop after this many generations without a better individual
+    pub stagnation: Option<usize>,
+    /// Threads evaluating the population, breeding stays on one thread so a run
+    /// does not depend on it
+    pub threads: usize,
+    selection: Box<dyn Selection>,
+    crossover: Box<dyn Crossover>,
+    mutation: Box<dyn Mutation>,
+    rng: StdRng,
+    pop: Vec<Individual>,
+    fitness_vals: Vec<f64>,
+    best: Option<Individual>,
+    stalled: usize,
+    pub stats: Vec<GenStats>,
+}
+
+impl GeneticAlgorithm {
+    /// Binary tournament, uniform crossover of every pair and uniform mutation with
+    /// `p_m` 0.01, one elite and 100 generations
+    pub fn new(pop_size: usize, seed: u64) -> GeneticAlgorithm {
+        if pop_size < 2 {
+            panic!("pop_size must be at least 2");
+        }
+        GeneticAlgorithm {
+            pop_size,
+            elitism: 1,
+            crossover_rate: 1.0,
+            max_gen: 100,
+            target: None,
+            stagnation: None,
+            threads: 1,
+            selection: Box::new(Tournament::new(2)),
+            crossover: Box::new(crossover::Uniform),
+            mutation: Box::new(mutation::Uniform::new(0.01)),
+            rng: StdRng::seed_from_u64(seed),
+            pop: vec![],
+            fitness_vals: vec![],
+            best: None,
+            stalled: 0,
+            stats: vec![],
+        }
+    }
+
+    pub fn elitism(mut self, elitism: usize) -> GeneticAlgorithm {
+        if elitism >= self.pop_size {
+            panic!("elitism must be smaller than pop_size");
+        }
+        self.elitism = elitism;
+        self
+    }
+
+    pub fn crossover_rate(mut self, p: f64) -> GeneticAlgorithm {
+        if !(0.0..=1.0).contains(&p) {
+            panic!("crossover_rate must be in range [0, 1]");
+        }
+        self.crossover_rate = p;
+        self
+    }
+
+    pub fn max_gen(mut self, max_gen: usize) -> GeneticAlgorithm {
+        self.max_gen = max_gen;
+        self
+    }
+
+    pub fn target(mut self, fitness: f64) -> GeneticAlgorithm {
+        self.target = Some(fitness);
+        self
+    }
+
+    pub fn stagnation(mut self, generations: usize) -> GeneticAlgorithm {
+        self.stagnation = Some(generations);
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> GeneticAlgorithm {
+        if threads == 0 {
+            panic!("threads must be at least 1");
+        }
+        self.threads = threads;
+        self
+    }
+
+    pub fn selection(mut self, selection: Box<dyn Selection>) -> GeneticAlgorithm {
+        self.selection = selection;
+        self
+    }
+
+    pub fn crossover(mut self, crossover: Box<dyn Crossover>) -> GeneticAlgorithm {
+        self.crossover = crossover;
+        self
+    }
+
+    pub fn mutation(mut self, mutation: Box<dyn Mutation>) -> GeneticAlgorithm {
+        self.mutation = mutation;
+        self
+    }
+
+    /// New random population for the shape of `net`, the statistics are cleared
+    pub fn init(&mut self, net: &Net) {
+        self.pop = init_pop(net, self.pop_size as u32, &mut self.rng);
+        self.fitness_vals.clear();
+        self.best = None;
+        self.stalled = 0;
+        self.stats.clear();
+    }
+
+    /// Evaluate the population with `fitness` on clones of `net`, then breed the next
+    /// one. `net` is left with the best individual seen so far.
+    pub fn step(&mut self, net: &mut Net, fitness: &(dyn Fn(&mut Net) -> f64 + Sync)) -> &GenStats {
+        self.evaluate(net, fitness);
+        self.pop = self.breed();
+        assign_ind(net, self.best.as_ref().unwrap());
+        self.stats.last().unwrap()
+    }
 
-        l.b.iter_mut().for_each(|b_i| {
-            *b_i = individual.chromosome[idx];
-            idx += 1;
+    /// Set the fitness of the population, the best individual and the statistics
+    fn evaluate(&mut self, net: &Net, fitness: &(dyn Fn(&mut Net) -> f64 + Sync)) {
+        if self.pop.is_empty() {
+            panic!("init must be called before step");
+        }
+        let chromosomes: Vec<&[f64]> = self.pop.iter().map(|p| p.chromosome.as_slice()).collect();
+        self.fitness_vals = parallel::evaluate(net, &chromosomes, self.threads, fitness);
+        for (p, f) in self.pop.iter_mut().zip(self.fitness_vals.iter()) {
+            p.set_fitness(*f);
+        }
+
+        let gen_best = self.pop.iter().fold(&self.pop[0], |b, p| {
+            if cmp_fitness(p.fitness, b.fitness).is_gt() {
+                p
+            } else {
+                b
+            }
         });
+        match &self.best {
+            Some(b) if cmp_fitness(b.fitness, gen_best.fitness).is_ge() => self.stalled += 1,
+            _ => {
+                self.best = Some(gen_best.clone());
+                self.stalled = 0;
+            }
+        }
+        self.stats
+            .push(GenStats::new(self.stats.len(), &self.fitness_vals));
+    }
+
+    /// Elites and mutated children of the evaluated population
+    fn breed(&mut self) -> Vec<Individual> {
+        let mut ranked: Vec<&Individual> = self.pop.iter().collect();
+        ranked.sort_by(|a, b| cmp_fitness(b.fitness, a.fitness));
+        let mut new_pop: Vec<Individual> = ranked[..self.elitism]
+            .iter()
+            .map(|p| (*p).clone())
+            .collect();
+
+        let n_children = self.pop_size - self.elitism;
+        let parents = self
+            .selection
+            .select(&self.pop, n_children + n_children % 2, &mut self.rng);
+        let generation = self.stats.len();
+        for pair in parents.chunks(2) {
+            let children = offspring(
+                [&pair[0], &pair[1]],
+                self.crossover.as_ref(),
+                self.crossover_rate,
+                &mut self.rng,
+            );
+            for mut child in children {
+                if new_pop.len() < self.pop_size {
+                    self.mutation
+                        .mutate(&mut child, generation, self.max_gen, &mut self.rng);
+                    new_pop.push(child);
+                }
+            }
+        }
+        new_pop
+    }
+
+    /// Whether a termination criterion is met
+    pub fn done(&self) -> bool {
+        let stats = match self.stats.last() {
+            Some(s) => s,
+            None => return false,
+        };
+        self.stats.len() >= self.max_gen
+            || self.target.is_some_and(|t| stats.best >= t)
+            || self.stagnation.is_some_and(|s| self.stalled >= s)
+    }
+
+    /// Evolve `net` from a new population until `done`, `net` is left with the best
+    /// individual which is returned
+    pub fn run(&mut self, net: &mut Net, fitness: &(dyn Fn(&mut Net) -> f64 + Sync)) -> Individual {
+        self.init(net);
+        while !self.done() {
+            self.step(net, fitness);
+        }
+        self.best.clone().unwrap()
+    }
+
+    /// Best individual seen since `init`
+    pub fn best(&self) -> Option<&Individual> {
+        self.best.as_ref()
+    }
+
+    /// Fitness of every individual of the last evaluated generation
+    pub fn fitness(&self) -> &[f64] {
+        &self.fitness_vals
     }
 }
 
@@ -147,7 +427,7 @@ mod tests {
         layers.push(Layer::new(4, 2, 1.0, activator::sigmoid()));
         layers.push(Layer::new(2, 1, 1.0, activator::sigmoid()));
         let net = Net::from_layers(layers);
-        let pop = init_pop(&net, 5);
+        let pop = init_pop(&net, 5, &mut StdRng::seed_from_u64(0));
 
         assert_eq!(pop.len(), 5);
         assert_eq!(pop[0].chromosome.len() as u64, net.parameters);
@@ -170,11 +450,9 @@ mod tests {
         // check if network has been mutated correctly or not.
         let mut idx = 0;
         for l in net.layers {
-            for output in l.w {
-                for w in output {
-                    assert_eq!(w, individual.chromosome[idx]);
-                    idx += 1;
-                }
+            for w in l.w.as_slice() {
+                assert_eq!(*w, individual.chromosome[idx]);
+                idx += 1;
             }
             for b in l.b {
                 assert_eq!(b, individual.chromosome[idx]);
@@ -183,6 +461,93 @@ mod tests {
         }
     }
 
+    fn small_net() -> Net {
+        Net::from_layers(vec![Layer::new(2, 2, 1.0, activator::sigmoid())])
+    }
+
+    /// Highest when every parameter is 0.5
+    fn fitness(net: &mut Net) -> f64 {
+        -net.get_params()
+            .iter()
+            .map(|p| (p - 0.5).powi(2))
+            .sum::<f64>()
+    }
+
+    #[test]
+    fn test_ga_run() {
+        let mut net = small_net();
+        let mut ga = GeneticAlgorithm::new(20, 1)
+            .elitism(2)
+            .max_gen(60)
+            .mutation(Box::new(mutation::Uniform::new(0.2)));
+        let best = ga.run(&mut net, &fitness);
+        assert_eq!(ga.stats.len(), 60);
+        assert!(best.fitness > ga.stats[0].best);
+        assert_eq!(net.get_params(), best.chromosome);
+        // elitism keeps the best individual
+        assert!(ga.stats.windows(2).all(|s| s[1].best >= s[0].best));
+
+        let mut other = GeneticAlgorithm::new(20, 1)
+            .elitism(2)
+            .max_gen(60)
+            .mutation(Box::new(mutation::Uniform::new(0.2)));
+        assert_eq!(
+            other.run(&mut small_net(), &fitness).chromosome,
+            best.chromosome
+        );
+    }
+
+    #[test]
+    fn test_ga_threads() {
+        let run = |threads: usize| {
+            let mut ga = GeneticAlgorithm::new(15, 3).max_gen(20).threads(threads);
+            let best = ga.run(&mut small_net(), &fitness);
+            (best.chromosome, ga.stats)
+        };
+        assert_eq!(run(1), run(4));
+    }
+
+    #[test]
+    fn test_ga_self_adaptive() {
+        let mut net = small_net();
+        let mut ga = GeneticAlgorithm::new(20, 2)
+            .max_gen(60)
+            .crossover(Box::new(crossover::Arithmetic { weight: None }))
+            .mutation(Box::new(mutation::SelfAdaptive::new(6, 0.1)));
+        let best = ga.run(&mut net, &fitness);
+        assert!(best.fitness > ga.stats[0].best);
+        assert_eq!(best.sigma.len(), 6);
+    }
+
+    #[test]
+    fn test_mutate_nonuni() {
+        let pop: Vec<Individual> = (0..10)
+            .map(|i| Individual::new(vec![i as f64; 50]))
+            .collect();
+        let mutated = mutate_nonuni(&pop, 4, 1.0, 50, 100);
+        assert_eq!(mutated.len(), 4);
+        // still mutating half way through the run
+        assert!(mutated
+            .iter()
+            .all(|m| m.chromosome.iter().any(|g| g.fract() != 0.0)));
+        let last = mutate_nonuni(&pop, 4, 1.0, 100, 100);
+        assert!(last
+            .iter()
+            .all(|m| m.chromosome.iter().all(|g| g.fract() == 0.0)));
+    }
+
+    #[test]
+    fn test_ga_termination() {
+        let mut net = small_net();
+        let mut ga = GeneticAlgorithm::new(4, 0).target(1.0);
+        ga.run(&mut net, &|_: &mut Net| 1.0);
+        assert_eq!(ga.stats.len(), 1);
+
+        let mut ga = GeneticAlgorithm::new(4, 0).stagnation(3);
+        ga.run(&mut net, &|_: &mut Net| 1.0);
+        assert_eq!(ga.stats.len(), 4);
+    }
+
     #[test]
     fn test_mating_and_mutate() {
         let mut pop: Vec<Individual> = vec![];
@@ -0,0 +1,233 @@
+//! Random changes to the genes of an offspring.
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use super::Individual;
+
+pub trait Mutation {
+    /// Mutate `ind` in place, `generation` of `max_gen` lets the strength change over
+    /// the run
+    fn mutate(&self, ind: &mut Individual, generation: usize, max_gen: usize, rng: &mut StdRng);
+}
+
+/// Add a change in [-`range`, `range`] to every gene with probability `p_m`
+pub struct Uniform {
+    pub p_m: f64,
+    pub range: f64,
+}
+
+impl Uniform {
+    pub fn new(p_m: f64) -> Uniform {
+        Uniform { p_m, range: 1.0 }
+    }
+}
+
+impl Mutation for Uniform {
+    fn mutate(&self, ind: &mut Individual, _generation: usize, _max_gen: usize, rng: &mut StdRng) {
+        for gene in ind.chromosome.iter_mut() {
+            if rng.gen_bool(self.p_m) {
+                *gene += rng.gen_range(-self.range..=self.range);
+            }
+        }
+    }
+}
+
+/// How the mutation strength changes from 1 at the first generation to the last
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Anneal {
+    Constant,
+    /// Down to 0 at `max_gen`
+    Linear,
+    /// Geometric decay down to `end` at `max_gen`
+    Exponential(f64),
+    /// Half cosine down to 0 at `max_gen`
+    Cosine,
+}
+
+impl Anneal {
+    pub fn factor(&self, generation: usize, max_gen: usize) -> f64 {
+        let t = if max_gen == 0 {
+            0.0
+        } else {
+            (generation as f64 / max_gen as f64).min(1.0)
+        };
+        match self {
+            Anneal::Constant => 1.0,
+            Anneal::Linear => 1.0 - t,
+            Anneal::Exponential(end) => end.powf(t),
+            Anneal::Cosine => 0.5 * (1.0 + (std::f64::consts::PI * t).cos()),
+        }
+    }
+}
+
+/// Standard normal sample (Box-Muller)
+pub fn standard_normal(rng: &mut StdRng) -> f64 {
+    let u: f64 = 1.0 - rng.gen::<f64>();
+    let v: f64 = rng.gen();
+    (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+}
+
+/// Standard Cauchy sample, heavy tailed so some changes are much larger than `scale`
+pub fn standard_cauchy(rng: &mut StdRng) -> f64 {
+    (std::f64::consts::PI * (rng.gen::<f64>() - 0.5)).tan()
+}
+
+/// Add a normal change of standard deviation `sigma` (annealed) to every gene with
+/// probability `p_m`
+pub struct Gaussian {
+    pub p_m: f64,
+    pub sigma: f64,
+    pub anneal: Anneal,
+}
+
+impl Gaussian {
+    pub fn new(p_m: f64, sigma: f64) -> Gaussian {
+        Gaussian {
+            p_m,
+            sigma,
+            anneal: Anneal::Constant,
+        }
+    }
+}
+
+impl Mutation for Gaussian {
+    fn mutate(&self, ind: &mut Individual, generation: usize, max_gen: usize, rng: &mut StdRng) {
+        let sigma = self.sigma * self.anneal.factor(generation, max_gen);
+        for gene in ind.chromosome.iter_mut() {
+            if rng.gen_bool(self.p_m) {
+                *gene += sigma * standard_normal(rng);
+            }
+        }
+    }
+}
+
+/// Same as `Gaussian` with a Cauchy change of scale `scale`, for longer jumps out of
+/// local optima
+pub struct Cauchy {
+    pub p_m: f64,
+    pub scale: f64,
+    pub anneal: Anneal,
+}
+
+impl Cauchy {
+    pub fn new(p_m: f64, scale: f64) -> Cauchy {
+        Cauchy {
+            p_m,
+            scale,
+            anneal: Anneal::Constant,
+        }
+    }
+}
+
+impl Mutation for Cauchy {
+    fn mutate(&self, ind: &mut Individual, generation: usize, max_gen: usize, rng: &mut StdRng) {
+        let scale = self.scale * self.anneal.factor(generation, max_gen);
+        for gene in ind.chromosome.iter_mut() {
+            if rng.gen_bool(self.p_m) {
+                *gene += scale * standard_cauchy(rng);
+            }
+        }
+    }
+}
+
+/// Evolution strategy mutation with a step size per gene stored in
+/// `Individual::sigma`: the step sizes are mutated log-normally first, then every gene
+/// gets a normal change of its own step size. Good step sizes survive with the genes
+/// they work for.
+pub struct SelfAdaptive {
+    /// Learning rate of each step size
+    pub tau: f64,
+    /// Learning rate shared by all the step sizes of an individual
+    pub tau_global: f64,
+    /// Step size of individuals that have none yet
+    pub initial: f64,
+    pub min_sigma: f64,
+}
+
+impl SelfAdaptive {
+    /// Usual learning rates for `genes` genes
+    pub fn new(genes: usize, initial: f64) -> SelfAdaptive {
+        let n = genes as f64;
+        SelfAdaptive {
+            tau: 1.0 / (2.0 * n.sqrt()).sqrt(),
+            tau_global: 1.0 / (2.0 * n).sqrt(),
+            initial,
+            min_sigma: 1e-6,
+        }
+    }
+}
+
+impl Mutation for SelfAdaptive {
+    fn mutate(&self, ind: &mut Individual, _generation: usize, _max_gen: usize, rng: &mut StdRng) {
+        if ind.sigma.len() != ind.chromosome.len() {
+            ind.sigma = vec![self.initial; ind.chromosome.len()];
+        }
+        let global = self.tau_global * standard_normal(rng);
+        for (gene, sigma) in ind.chromosome.iter_mut().zip(ind.sigma.iter_mut()) {
+            *sigma =
+                (*sigma * (global + self.tau * standard_normal(rng)).exp()).max(self.min_sigma);
+            *gene += *sigma * standard_normal(rng);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    /// Changes of every gene of a zero chromosome after one mutation
+    fn changes(mutation: &dyn Mutation, generation: usize) -> Vec<f64> {
+        let mut ind = Individual::new(vec![0.0; 10000]);
+        mutation.mutate(&mut ind, generation, 100, &mut StdRng::seed_from_u64(5));
+        ind.chromosome
+    }
+
+    fn std(v: &[f64]) -> f64 {
+        let mean = v.iter().sum::<f64>() / v.len() as f64;
+        (v.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / v.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn test_anneal() {
+        assert_eq!(Anneal::Linear.factor(25, 100), 0.75);
+        assert_eq!(Anneal::Cosine.factor(100, 100), 0.0);
+        assert!((Anneal::Exponential(0.01).factor(50, 100) - 0.1).abs() < 1e-12);
+        // still mutating half way, unlike exp(-generation)
+        assert!(Anneal::Exponential(0.01).factor(99, 100) > 0.01);
+    }
+
+    #[test]
+    fn test_gaussian_and_cauchy() {
+        let mut g = Gaussian::new(1.0, 0.5);
+        assert!((std(&changes(&g, 0)) - 0.5).abs() < 0.02);
+        g.anneal = Anneal::Linear;
+        assert!((std(&changes(&g, 50)) - 0.25).abs() < 0.01);
+        assert!(changes(&g, 100).iter().all(|c| *c == 0.0));
+
+        let g = Gaussian::new(0.1, 0.5);
+        let mutated = changes(&g, 0).iter().filter(|c| **c != 0.0).count();
+        assert!((mutated as f64 - 1000.0).abs() < 100.0);
+
+        // half of the cauchy changes are within the scale
+        let mut c: Vec<f64> = changes(&Cauchy::new(1.0, 0.5), 0)
+            .iter()
+            .map(|c| c.abs())
+            .collect();
+        c.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert!((c[5000] - 0.5).abs() < 0.03);
+    }
+
+    #[test]
+    fn test_self_adaptive() {
+        let m = SelfAdaptive::new(4, 0.1);
+        let mut ind = Individual::new(vec![0.0; 4]);
+        let mut rng = StdRng::seed_from_u64(0);
+        m.mutate(&mut ind, 0, 10, &mut rng);
+        assert_eq!(ind.sigma.len(), 4);
+        assert!(ind.sigma.iter().all(|s| *s != 0.1 && *s > 0.0));
+        let sigma = ind.sigma.clone();
+        m.mutate(&mut ind, 1, 10, &mut rng);
+        assert_ne!(ind.sigma, sigma);
+    }
+}
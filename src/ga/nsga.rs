@@ -0,0 +1,388 @@
+//! NSGA-II: evolution of several objectives at once, keeping the individuals no other
+//! one beats on every objective. Every objective is maximized.
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::crossover::{self, Crossover};
+use super::mutation::{self, Mutation};
+use super::{cmp_fitness, init_pop, offspring, Individual};
+use crate::mlp::Net;
+use crate::utills::parallel;
+
+/// Whether objectives `a` dominate `b`: no worse on any objective and better on one
+pub fn dominates(a: &[f64], b: &[f64]) -> bool {
+    a.iter().zip(b.iter()).all(|(x, y)| x >= y) && a.iter().zip(b.iter()).any(|(x, y)| x > y)
+}
+
+/// Indices of every front: the first is dominated by nobody, the next one by nobody
+/// once the fronts before it are removed
+pub fn non_dominated_sort(objectives: &[Vec<f64>]) -> Vec<Vec<usize>> {
+    let n = objectives.len();
+    // individuals dominated by each one, and how many dominate each one
+    let mut dominated: Vec<Vec<usize>> = vec![vec![]; n];
+    let mut count = vec![0; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            if dominates(&objectives[i], &objectives[j]) {
+                dominated[i].push(j);
+                count[j] += 1;
+            } else if dominates(&objectives[j], &objectives[i]) {
+                dominated[j].push(i);
+                count[i] += 1;
+            }
+        }
+    }
+
+    let mut fronts = vec![];
+    let mut front: Vec<usize> = (0..n).filter(|&i| count[i] == 0).collect();
+    while !front.is_empty() {
+        let mut next = vec![];
+        for &i in front.iter() {
+            for &j in dominated[i].iter() {
+                count[j] -= 1;
+                if count[j] == 0 {
+                    next.push(j);
+                }
+            }
+        }
+        next.sort_unstable();
+        fronts.push(front);
+        front = next;
+    }
+    fronts
+}
+
+/// Crowding distance of every member of `front`: the normalized size of the box
+/// between its neighbours on each objective, infinite at the ends. An objective with
+/// a non-finite range (NaN or infinite values) only marks its ends.
+pub fn crowding_distance(objectives: &[Vec<f64>], front: &[usize]) -> Vec<f64> {
+    let mut distance = vec![0.0; front.len()];
+    if front.is_empty() {
+        return distance;
+    }
+    // values of each objective over the front
+    let columns = (0..objectives[front[0]].len()).map(|k| {
+        front
+            .iter()
+            .map(|&i| objectives[i][k])
+            .collect::<Vec<f64>>()
+    });
+    for values in columns {
+        let mut order: Vec<usize> = (0..front.len()).collect();
+        order.sort_by(|&a, &b| cmp_fitness(values[a], values[b]));
+        let (first, last) = (order[0], order[order.len() - 1]);
+        let range = values[last] - values[first];
+        distance[first] = f64::INFINITY;
+        distance[last] = f64::INFINITY;
+        if range.is_finite() && range > 0.0 {
+            for w in order.windows(3) {
+                distance[w[1]] += (values[w[2]] - values[w[0]]) / range;
+            }
+        }
+    }
+    distance
+}
+
+/// Front index (rank) and crowding distance of every individual
+fn rank(objectives: &[Vec<f64>]) -> (Vec<usize>, Vec<f64>) {
+    let mut ranks = vec![0; objectives.len()];
+    let mut crowding = vec![0.0; objectives.len()];
+    for (r, front) in non_dominated_sort(objectives).iter().enumerate() {
+        for (&i, d) in front.iter().zip(crowding_distance(objectives, front)) {
+            ranks[i] = r;
+            crowding[i] = d;
+        }
+    }
+    (ranks, crowding)
+}
+
+/// NSGA-II of the net parameters. Parents are chosen by binary tournament on rank,
+/// then crowding distance. Parents and children are sorted the same way and the best
+/// `pop_size` of them make the next generation.
+///
+/// `init` then `step` until `done`, or `run` to do all of it.
+pub struct Nsga2 {
+    pub pop_size: usize,
+    /// Probability that a pair of parents is crossed, else the children are copies
+    pub crossover_rate: f64,
+    pub max_gen: usize,
+    /// Threads evaluating the population
+    pub threads: usize,
+    crossover: Box<dyn Crossover>,
+    mutation: Box<dyn Mutation>,
+    rng: StdRng,
+    pop: Vec<Individual>,
+    objectives: Vec<Vec<f64>>,
+    ranks: Vec<usize>,
+    crowding: Vec<f64>,
+    generation: usize,
+}
+
+impl Nsga2 {
+    /// Uniform crossover of every pair and uniform mutation with `p_m` 0.01 for 100
+    /// generations
+    pub fn new(pop_size: usize, seed: u64) -> Nsga2 {
+        if pop_size < 2 {
+            panic!("pop_size must be at least 2");
+        }
+        Nsga2 {
+            pop_size,
+            crossover_rate: 1.0,
+            max_gen: 100,
+            threads: 1,
+            crossover: Box::new(crossover::Uniform),
+            mutation: Box::new(mutation::Uniform::new(0.01)),
+            rng: StdRng::seed_from_u64(seed),
+            pop: vec![],
+            objectives: vec![],
+            ranks: vec![],
+            crowding: vec![],
+            generation: 0,
+        }
+    }
+
+    pub fn crossover_rate(mut self, p: f64) -> Nsga2 {
+        if !(0.0..=1.0).contains(&p) {
+            panic!("crossover_rate must be in range [0, 1]");
+        }
+        self.crossover_rate = p;
+        self
+    }
+
+    pub fn max_gen(mut self, max_gen: usize) -> Nsga2 {
+        self.max_gen = max_gen;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Nsga2 {
+        if threads == 0 {
+            panic!("threads must be at least 1");
+        }
+        self.threads = threads;
+        self
+    }
+
+    pub fn crossover(mut self, crossover: Box<dyn Crossover>) -> Nsga2 {
+        self.crossover = crossover;
+        self
+    }
+
+    pub fn mutation(mut self, mutation: Box<dyn Mutation>) -> Nsga2 {
+        self.mutation = mutation;
+        self
+    }
+
+    /// New random population for the shape of `net`
+    pub fn init(&mut self, net: &Net) {
+        self.pop = init_pop(net, self.pop_size as u32, &mut self.rng);
+        self.objectives.clear();
+        self.ranks.clear();
+        self.crowding.clear();
+        self.generation = 0;
+    }
+
+    /// Breed children of the population and keep the best of both. `objectives`
+    /// gives the same number of values for every net, on clones of `net`.
+    pub fn step(&mut self, net: &Net, objectives: &(dyn Fn(&mut Net) -> Vec<f64> + Sync)) {
+        if self.pop.is_empty() {
+            panic!("init must be called before step");
+        }
+        if self.objectives.is_empty() {
+            self.objectives = self.evaluate(net, &self.pop, objectives);
+            (self.ranks, self.crowding) = rank(&self.objectives);
+        }
+
+        let children = self.breed();
+        let children_objectives = self.evaluate(net, &children, objectives);
+        let mut pool = std::mem::take(&mut self.pop);
+        pool.extend(children);
+        let mut pool_objectives = std::mem::take(&mut self.objectives);
+        pool_objectives.extend(children_objectives);
+
+        let (ranks, crowding) = rank(&pool_objectives);
+        let mut order: Vec<usize> = (0..pool.len()).collect();
+        order.sort_by(|&a, &b| {
+            ranks[a]
+                .cmp(&ranks[b])
+                .then(crowding[b].total_cmp(&crowding[a]))
+        });
+        order.truncate(self.pop_size);
+
+        self.pop = order.iter().map(|&i| pool[i].clone()).collect();
+        self.objectives = order.iter().map(|&i| pool_objectives[i].clone()).collect();
+        self.ranks = order.iter().map(|&i| ranks[i]).collect();
+        self.crowding = order.iter().map(|&i| crowding[i]).collect();
+        self.generation += 1;
+    }
+
+    fn evaluate(
+        &self,
+        net: &Net,
+        pop: &[Individual],
+        objectives: &(dyn Fn(&mut Net) -> Vec<f64> + Sync),
+    ) -> Vec<Vec<f64>> {
+        let chromosomes: Vec<&[f64]> = pop.iter().map(|p| p.chromosome.as_slice()).collect();
+        parallel::evaluate(net, &chromosomes, self.threads, objectives)
+    }
+
+    /// Winner of a binary tournament on rank, then crowding distance
+    fn tournament(&mut self) -> usize {
+        let a = self.rng.gen_range(0..self.pop.len());
+        let b = self.rng.gen_range(0..self.pop.len());
+        if self.ranks[a] < self.ranks[b]
+            || (self.ranks[a] == self.ranks[b] && self.crowding[a] > self.crowding[b])
+        {
+            a
+        } else {
+            b
+        }
+    }
+
+    /// `pop_size` mutated children of the population
+    fn breed(&mut self) -> Vec<Individual> {
+        let mut children = Vec::with_capacity(self.pop_size);
+        while children.len() < self.pop_size {
+            let (a, b) = (self.tournament(), self.tournament());
+            let pair = offspring(
+                [&self.pop[a], &self.pop[b]],
+                self.crossover.as_ref(),
+                self.crossover_rate,
+                &mut self.rng,
+            );
+            for mut child in pair {
+                if children.len() < self.pop_size {
+                    self.mutation
+                        .mutate(&mut child, self.generation, self.max_gen, &mut self.rng);
+                    children.push(child);
+                }
+            }
+        }
+        children
+    }
+
+    pub fn done(&self) -> bool {
+        self.generation >= self.max_gen
+    }
+
+    /// Evolve a new population of the shape of `net` until `done` and return its
+    /// Pareto front
+    pub fn run(
+        &mut self,
+        net: &Net,
+        objectives: &(dyn Fn(&mut Net) -> Vec<f64> + Sync),
+    ) -> Vec<(Individual, Vec<f64>)> {
+        self.init(net);
+        while !self.done() {
+            self.step(net, objectives);
+        }
+        self.pareto_front()
+            .into_iter()
+            .map(|(ind, o)| (ind.clone(), o.to_vec()))
+            .collect()
+    }
+
+    /// Non-dominated individuals of the population with their objectives, by
+    /// decreasing first objective
+    pub fn pareto_front(&self) -> Vec<(&Individual, &[f64])> {
+        let mut front: Vec<(&Individual, &[f64])> = self
+            .pop
+            .iter()
+            .zip(self.objectives.iter())
+            .zip(self.ranks.iter())
+            .filter(|(_, &r)| r == 0)
+            .map(|((ind, o), _)| (ind, o.as_slice()))
+            .collect();
+        front.sort_by(|a, b| cmp_fitness(b.1[0], a.1[0]));
+        front
+    }
+
+    /// Objectives of every individual of the population
+    pub fn objectives(&self) -> &[Vec<f64>] {
+        &self.objectives
+    }
+
+    /// Generations since `init`
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{activator, mlp::Layer};
+
+    #[test]
+    fn test_non_dominated_sort() {
+        let objectives = vec![
+            vec![1.0, 1.0],
+            vec![2.0, 2.0],
+            vec![0.0, 3.0],
+            vec![2.0, 0.0],
+            vec![0.0, 0.0],
+        ];
+        assert!(dominates(&objectives[1], &objectives[3]));
+        assert!(!dominates(&objectives[1], &objectives[2]));
+        assert!(!dominates(&objectives[1], &objectives[1]));
+        assert_eq!(
+            non_dominated_sort(&objectives),
+            vec![vec![1, 2], vec![0, 3], vec![4]]
+        );
+    }
+
+    #[test]
+    fn test_crowding_distance() {
+        let objectives = vec![
+            vec![2.0, 1.0],
+            vec![0.0, 3.0],
+            vec![3.0, 0.0],
+            vec![1.0, 2.0],
+        ];
+        let d = crowding_distance(&objectives, &[0, 1, 2, 3]);
+        assert_eq!(d[1], f64::INFINITY);
+        assert_eq!(d[2], f64::INFINITY);
+        assert!((d[0] - 4.0 / 3.0).abs() < 1e-12);
+        assert!((d[3] - 4.0 / 3.0).abs() < 1e-12);
+
+        // a diverged net: NaN and infinite objectives leave no NaN distance
+        let objectives = vec![
+            vec![f64::NAN, 1.0],
+            vec![1.0, f64::INFINITY],
+            vec![2.0, 0.0],
+            vec![3.0, 2.0],
+            vec![4.0, 3.0],
+        ];
+        let d = crowding_distance(&objectives, &[0, 1, 2, 3, 4]);
+        assert_eq!(d[3], 0.0);
+        assert!([0, 1, 2, 4].iter().all(|&i| d[i] == f64::INFINITY));
+    }
+
+    #[test]
+    fn test_nsga_run() {
+        // every parameter is wanted at 0.5 and at -0.5
+        let objectives = |net: &mut Net| -> Vec<f64> {
+            let params = net.get_params();
+            vec![
+                -params.iter().map(|p| (p - 0.5).powi(2)).sum::<f64>(),
+                -params.iter().map(|p| (p + 0.5).powi(2)).sum::<f64>(),
+            ]
+        };
+        let net = Net::from_layers(vec![Layer::new(2, 2, 1.0, activator::sigmoid())]);
+        let run = |threads: usize| {
+            let mut nsga = Nsga2::new(20, 5).max_gen(30).threads(threads);
+            let front = nsga.run(&net, &objectives);
+            assert_eq!(nsga.objectives().len(), 20);
+            front
+        };
+        let front = run(1);
+        assert!(front.len() > 2);
+        for (_, a) in front.iter() {
+            assert!(front.iter().all(|(_, b)| !dominates(b, a)));
+        }
+        assert!(front.windows(2).all(|w| w[0].1[0] >= w[1].1[0]));
+        let chromosomes = |f: &[(Individual, Vec<f64>)]| -> Vec<Vec<f64>> {
+            f.iter().map(|(ind, _)| ind.chromosome.clone()).collect()
+        };
+        assert_eq!(chromosomes(&run(4)), chromosomes(&front));
+    }
+}
@@ -1,6 +1,9 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
+use rand::Rng;
 
-use super::Individual;
+use super::{cmp_fitness, Individual};
 
 /// binary deterministic tournament with reinsertion
 pub fn d_tornament(pop: &Vec<Individual>) -> Vec<Individual> {
@@ -16,3 +19,377 @@ pub fn d_tornament(pop: &Vec<Individual>) -> Vec<Individual> {
     }
     results
 }
+
+/// Chooses the parents of the next generation from an evaluated population,
+/// higher fitness is better
+pub trait Selection {
+    fn select(&self, pop: &[Individual], n: usize, rng: &mut StdRng) -> Vec<Individual>;
+}
+
+/// Tournament between `k` distinct individuals: the fittest wins with probability `p`,
+/// else the second with `p`, and so on, the last player wins what is left. `p` is 1 by
+/// default (deterministic). Players go back in the pool after each tournament.
+pub struct Tournament {
+    pub k: usize,
+    pub p: f64,
+}
+
+impl Tournament {
+    pub fn new(k: usize) -> Tournament {
+        if k == 0 {
+            panic!("k must be greater than 0");
+        }
+        Tournament { k, p: 1.0 }
+    }
+}
+
+impl Selection for Tournament {
+    fn select(&self, pop: &[Individual], n: usize, rng: &mut StdRng) -> Vec<Individual> {
+        (0..n)
+            .map(|_| {
+                let mut players: Vec<&Individual> = pop.choose_multiple(rng, self.k).collect();
+                players.sort_by(|a, b| cmp_fitness(b.fitness, a.fitness));
+                let last = players.len() - 1;
+                let winner = (0..last).find(|_| rng.gen_bool(self.p)).unwrap_or(last);
+                players[winner].clone()
+            })
+            .collect()
+    }
+}
+
+/// Indices of `pop` from the worst to the best fitness
+fn ranked(pop: &[Individual]) -> Vec<usize> {
+    let mut idx: Vec<usize> = (0..pop.len()).collect();
+    idx.sort_by(|&a, &b| cmp_fitness(pop[a].fitness, pop[b].fitness));
+    idx
+}
+
+/// Fitness shifted so the worst finite one has weight 0, NaN and -inf get 0 and +inf
+/// an infinite weight
+fn shifted(pop: &[Individual]) -> Vec<f64> {
+    let worst = pop
+        .iter()
+        .map(|p| p.fitness)
+        .filter(|f| f.is_finite())
+        .fold(f64::MAX, f64::min);
+    pop.iter()
+        .map(|p| {
+            if p.fitness.is_nan() {
+                0.0
+            } else {
+                (p.fitness - worst).max(0.0)
+            }
+        })
+        .collect()
+}
+
+/// Weights a wheel can use: infinite weights share all the probability, NaN and
+/// negative ones get none, uniform when every weight is 0
+fn finite_weights(weights: &[f64]) -> Vec<f64> {
+    let w: Vec<f64> = if weights.contains(&f64::INFINITY) {
+        weights
+            .iter()
+            .map(|w| if *w == f64::INFINITY { 1.0 } else { 0.0 })
+            .collect()
+    } else {
+        weights
+            .iter()
+            .map(|w| if *w > 0.0 { *w } else { 0.0 })
+            .collect()
+    };
+    if w.iter().all(|w| *w == 0.0) {
+        vec![1.0; w.len()]
+    } else {
+        w
+    }
+}
+
+/// `n` independent draws of `pop` with probabilities proportional to `weights`
+fn spin(pop: &[Individual], weights: &[f64], n: usize, rng: &mut StdRng) -> Vec<Individual> {
+    let dist = WeightedIndex::new(finite_weights(weights)).unwrap();
+    (0..n).map(|_| pop[dist.sample(rng)].clone()).collect()
+}
+
+/// Fitness proportional selection, on fitness shifted by the worst one so negative
+/// fitness works
+pub struct Roulette;
+
+impl Selection for Roulette {
+    fn select(&self, pop: &[Individual], n: usize, rng: &mut StdRng) -> Vec<Individual> {
+        spin(pop, &shifted(pop), n, rng)
+    }
+}
+
+/// Stochastic universal sampling: one spin of a wheel with `n` evenly spaced
+/// pointers over the shifted fitness, every individual gets its expected number of
+/// copies rounded up or down
+pub struct StochasticUniversal;
+
+impl Selection for StochasticUniversal {
+    fn select(&self, pop: &[Individual], n: usize, rng: &mut StdRng) -> Vec<Individual> {
+        let weights = finite_weights(&shifted(pop));
+        let step = weights.iter().sum::<f64>() / n as f64;
+        let mut pointer = rng.gen_range(0.0..step);
+        let (mut i, mut cumulative) = (0, weights[0]);
+        let mut results: Vec<Individual> = vec![];
+        for _ in 0..n {
+            while cumulative <= pointer && i < pop.len() - 1 {
+                i += 1;
+                cumulative += weights[i];
+            }
+            results.push(pop[i].clone());
+            pointer += step;
+        }
+        results.shuffle(rng);
+        results
+    }
+}
+
+/// Linear ranking: the best individual is expected `s` times and the worst `2 - s`
+/// times per `pop.len()` draws, `s` in [1, 2]
+pub struct LinearRank {
+    pub s: f64,
+}
+
+impl LinearRank {
+    pub fn new(s: f64) -> LinearRank {
+        if !(1.0..=2.0).contains(&s) {
+            panic!("s must be in range [1, 2]");
+        }
+        LinearRank { s }
+    }
+}
+
+impl Selection for LinearRank {
+    fn select(&self, pop: &[Individual], n: usize, rng: &mut StdRng) -> Vec<Individual> {
+        let size = pop.len() as f64;
+        let mut weights = vec![0.0; pop.len()];
+        for (rank, i) in ranked(pop).into_iter().enumerate() {
+            weights[i] = if pop.len() == 1 {
+                1.0
+            } else {
+                (2.0 - self.s) + 2.0 * rank as f64 * (self.s - 1.0) / (size - 1.0)
+            };
+        }
+        spin(pop, &weights, n, rng)
+    }
+}
+
+/// Exponential ranking: each rank is `c` times as likely as the next better one,
+/// `c` in (0, 1), smaller is greedier
+pub struct ExponentialRank {
+    pub c: f64,
+}
+
+impl ExponentialRank {
+    pub fn new(c: f64) -> ExponentialRank {
+        if c <= 0.0 || c >= 1.0 {
+            panic!("c must be in range (0, 1)");
+        }
+        ExponentialRank { c }
+    }
+}
+
+impl Selection for ExponentialRank {
+    fn select(&self, pop: &[Individual], n: usize, rng: &mut StdRng) -> Vec<Individual> {
+        let last = pop.len() - 1;
+        let mut weights = vec![0.0; pop.len()];
+        for (rank, i) in ranked(pop).into_iter().enumerate() {
+            weights[i] = self.c.powi((last - rank) as i32);
+        }
+        spin(pop, &weights, n, rng)
+    }
+}
+
+/// Uniform draws among the fittest `fraction` of the population
+pub struct Truncation {
+    pub fraction: f64,
+}
+
+impl Truncation {
+    pub fn new(fraction: f64) -> Truncation {
+        if fraction <= 0.0 || fraction > 1.0 {
+            panic!("fraction must be in range (0, 1]");
+        }
+        Truncation { fraction }
+    }
+}
+
+impl Selection for Truncation {
+    fn select(&self, pop: &[Individual], n: usize, rng: &mut StdRng) -> Vec<Individual> {
+        let keep = ((self.fraction * pop.len() as f64).ceil() as usize).max(1);
+        let best: Vec<usize> = ranked(pop).into_iter().rev().take(keep).collect();
+        (0..n)
+            .map(|_| pop[*best.choose(rng).unwrap()].clone())
+            .collect()
+    }
+}
+
+/// Probabilities proportional to `exp(fitness / temperature)`: close to uniform when
+/// hot, close to always the best when cold
+pub struct Boltzmann {
+    pub temperature: f64,
+}
+
+impl Boltzmann {
+    pub fn new(temperature: f64) -> Boltzmann {
+        if temperature <= 0.0 {
+            panic!("temperature must be greater than 0");
+        }
+        Boltzmann { temperature }
+    }
+}
+
+impl Selection for Boltzmann {
+    fn select(&self, pop: &[Individual], n: usize, rng: &mut StdRng) -> Vec<Individual> {
+        // shifted by the best finite fitness so exp does not overflow
+        let best = pop
+            .iter()
+            .map(|p| p.fitness)
+            .filter(|f| f.is_finite())
+            .fold(f64::MIN, f64::max);
+        let weights: Vec<f64> = pop
+            .iter()
+            .map(|p| ((p.fitness - best) / self.temperature).exp())
+            .collect();
+        spin(pop, &weights, n, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    const DRAWS: usize = 20000;
+
+    /// Individuals with fitness 0 to 9, the fitness is also their index
+    fn pop() -> Vec<Individual> {
+        (0..10)
+            .map(|i| {
+                let mut ind = Individual::new(vec![i as f64]);
+                ind.set_fitness(i as f64);
+                ind
+            })
+            .collect()
+    }
+
+    /// Share of the draws of `selection` that picked each individual
+    fn shares(selection: &dyn Selection) -> Vec<f64> {
+        let mut counts = vec![0.0; 10];
+        for ind in selection.select(&pop(), DRAWS, &mut StdRng::seed_from_u64(3)) {
+            counts[ind.fitness as usize] += 1.0 / DRAWS as f64;
+        }
+        counts
+    }
+
+    fn mean_fitness(selection: &dyn Selection) -> f64 {
+        shares(selection)
+            .iter()
+            .enumerate()
+            .map(|(i, s)| i as f64 * s)
+            .sum()
+    }
+
+    fn assert_close(shares: &[f64], expected: &[f64]) {
+        for (s, e) in shares.iter().zip(expected.iter()) {
+            assert!((s - e).abs() < 0.015, "{:?} != {:?}", shares, expected);
+        }
+    }
+
+    #[test]
+    fn test_proportional() {
+        let expected: Vec<f64> = (0..10).map(|i| i as f64 / 45.0).collect();
+        assert_close(&shares(&Roulette), &expected);
+
+        // every individual gets 10 * i / 45 copies rounded up or down
+        let sus = StochasticUniversal.select(&pop(), 10, &mut StdRng::seed_from_u64(1));
+        for i in 0..10 {
+            let copies = sus.iter().filter(|p| p.fitness == i as f64).count() as f64;
+            assert!((copies - 10.0 * i as f64 / 45.0).abs() < 1.0);
+        }
+    }
+
+    #[test]
+    fn test_rank() {
+        let linear = shares(&LinearRank::new(2.0));
+        assert_eq!(linear[0], 0.0);
+        assert_close(
+            &linear,
+            &(0..10).map(|i| i as f64 / 45.0).collect::<Vec<f64>>(),
+        );
+        // s = 1 is uniform
+        assert_close(&shares(&LinearRank::new(1.0)), &[0.1; 10]);
+
+        let exp = shares(&ExponentialRank::new(0.5));
+        assert!((exp[9] - 0.5).abs() < 0.015);
+        assert!((exp[8] - 0.25).abs() < 0.015);
+    }
+
+    #[test]
+    fn test_truncation_and_boltzmann() {
+        let t = shares(&Truncation::new(0.3));
+        assert!(t[..7].iter().all(|s| *s == 0.0));
+        assert_close(&t[7..], &[1.0 / 3.0; 3]);
+
+        assert!(mean_fitness(&Boltzmann::new(100.0)) < 5.0);
+        assert!(shares(&Boltzmann::new(0.1))[9] > 0.99);
+    }
+
+    #[test]
+    fn test_non_finite_fitness() {
+        let pop: Vec<Individual> = [1.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 2.0]
+            .iter()
+            .map(|&f| {
+                let mut ind = Individual::new(vec![]);
+                ind.set_fitness(f);
+                ind
+            })
+            .collect();
+        let selections: Vec<Box<dyn Selection>> = vec![
+            Box::new(Tournament::new(5)),
+            Box::new(Roulette),
+            Box::new(StochasticUniversal),
+            Box::new(Truncation::new(0.2)),
+            Box::new(Boltzmann::new(1.0)),
+        ];
+        // +inf takes every draw, NaN is the worst individual
+        for selection in selections.iter() {
+            let chosen = selection.select(&pop, 20, &mut StdRng::seed_from_u64(0));
+            assert!(chosen.iter().all(|p| p.fitness == f64::INFINITY));
+        }
+        let ranks = ranked(&pop);
+        assert_eq!(ranks[0], 1);
+        assert_eq!(ranks[4], 2);
+        assert_eq!(
+            LinearRank::new(2.0)
+                .select(&pop, 20, &mut StdRng::seed_from_u64(0))
+                .len(),
+            20
+        );
+
+        // only NaN and -inf left, the wheel is uniform
+        let worst = [pop[1].clone(), pop[3].clone()];
+        for selection in [&Roulette as &dyn Selection, &StochasticUniversal] {
+            let chosen = selection.select(&worst, 20, &mut StdRng::seed_from_u64(0));
+            assert!(chosen.iter().any(|p| p.fitness.is_nan()));
+            assert!(chosen.iter().any(|p| p.fitness == f64::NEG_INFINITY));
+        }
+    }
+
+    #[test]
+    fn test_selection_pressure() {
+        // larger or more deterministic tournaments pick fitter parents
+        let mut t2 = Tournament::new(2);
+        let t4 = Tournament::new(4);
+        assert!(mean_fitness(&t4) > mean_fitness(&t2));
+        let deterministic = mean_fitness(&t2);
+        t2.p = 0.75;
+        assert!(mean_fitness(&t2) < deterministic);
+        // the worst individual can not win a deterministic tournament
+        assert_eq!(shares(&Tournament::new(2))[0], 0.0);
+
+        assert!(mean_fitness(&ExponentialRank::new(0.5)) > mean_fitness(&LinearRank::new(2.0)));
+        assert!(mean_fitness(&Boltzmann::new(1.0)) > mean_fitness(&Boltzmann::new(10.0)));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::matrix::Matrix;
 use crate::mlp;
 
 pub struct Loss {
@@ -5,6 +6,7 @@ pub struct Loss {
     desired: Vec<f64>,
     pub func: fn(f64, f64) -> f64,
     pub der: fn(f64, f64) -> f64,
+    pub name: String,
 }
 
 impl Loss {
@@ -26,6 +28,7 @@ impl Loss {
             desired: vec![],
             func,
             der,
+            name: "abs_err".to_string(),
         }
     }
 
@@ -43,6 +46,7 @@ impl Loss {
             desired: vec![],
             func,
             der,
+            name: "square_err".to_string(),
         }
     }
 
@@ -60,10 +64,29 @@ impl Loss {
             desired: vec![],
             func,
             der,
+            name: "bce".to_string(),
         }
     }
 
-    pub fn criterion(&mut self, outputs: &Vec<f64>, desired: &Vec<f64>) -> f64 {
+    /// Categorical Cross Entropy, for one-hot labels with a `activator::softmax` output
+    pub fn cce() -> Loss {
+        fn func(output: f64, desired: f64) -> f64 {
+            -desired * output.max(f64::MIN_POSITIVE).ln()
+        }
+        fn der(output: f64, desired: f64) -> f64 {
+            -desired / output.max(f64::MIN_POSITIVE)
+        }
+
+        Loss {
+            outputs: vec![],
+            desired: vec![],
+            func,
+            der,
+            name: "cce".to_string(),
+        }
+    }
+
+    pub fn criterion(&mut self, outputs: &[f64], desired: &[f64]) -> f64 {
         if outputs.len() != desired.len() {
             panic!("outputs size is not equal to desired size");
         }
@@ -71,53 +94,68 @@ impl Loss {
             .iter()
             .zip(desired.iter())
             .fold(0.0, |ls, (o, d)| ls + (self.func)(*o, *d));
-        self.outputs = outputs.clone();
-        self.desired = desired.clone();
+        self.outputs = outputs.to_vec();
+        self.desired = desired.to_vec();
         loss
     }
 
-    pub fn backward(&self, layers: &mut Vec<mlp::Layer>) {
+    /// Sum of the loss of every row (sample), nothing is kept for `backward`.
+    pub fn criterion_batch(&self, outputs: &Matrix, desired: &Matrix) -> f64 {
+        if outputs.rows() != desired.rows() || outputs.cols() != desired.cols() {
+            panic!("outputs size is not equal to desired size");
+        }
+        outputs
+            .as_slice()
+            .iter()
+            .zip(desired.as_slice().iter())
+            .fold(0.0, |ls, (o, d)| ls + (self.func)(*o, *d))
+    }
+
+    /// Accumulate gradients of the last `criterion` call into `layers`, the average is
+    /// applied on the next `Layer::step`. Works for any number of layers.
+    pub fn backward(&self, layers: &mut [mlp::Layer]) {
+        let last = layers.len() - 1;
+        // gradients w.r.t. the weighted sums of the output layer
+        let mut local_grads: Vec<f64> = if self.name == "cce" && layers[last].act.name == "softmax"
+        {
+            // softmax with cross entropy simplifies to output - desired
+            self.outputs
+                .iter()
+                .zip(self.desired.iter())
+                .map(|(o, d)| o - d)
+                .collect()
+        } else {
+            let grads: Vec<f64> = self
+                .outputs
+                .iter()
+                .zip(self.desired.iter())
+                .map(|(o, d)| (self.der)(*o, *d))
+                .collect();
+            layers[last].act.backward(&layers[last].outputs, &grads)
+        };
+
         for l in (0..layers.len()).rev() {
-            // output layer
-            if l == layers.len() - 1 {
-                for j in 0..layers[l].outputs.len() {
-                    // compute grads
-                    let local_grad = (self.der)(self.outputs[j], self.desired[j])
-                        * (layers[l].act.der)(layers[l].outputs[j]);
-
-                    layers[l].local_grads[j] = local_grad;
-
-                    // set grads for each weight
-                    for k in 0..(layers[l - 1].outputs.len()) {
-                        layers[l].grads[j][k] =
-                            (layers[l - 1].act.func)(layers[l - 1].outputs[k]) * local_grad;
-                    }
+            let layer = &mut layers[l];
+            // inputs of this layer are the activated outputs of the previous one
+            for (j, local_grad) in local_grads.iter().enumerate() {
+                layer.local_grads[j] = *local_grad;
+                layer.b_grads[j] += local_grad;
+                for (g_jk, x_k) in layer.grads.row_mut(j).iter_mut().zip(layer.inputs.iter()) {
+                    *g_jk += x_k * local_grad;
                 }
-                continue;
             }
-            // hidden layer
-            for j in 0..layers[l].outputs.len() {
-                // calculate local_grad based on previous local_grad
-                let mut local_grad = 0f64;
-                for i in 0..layers[l + 1].w.len() {
-                    for k in 0..layers[l + 1].w[i].len() {
-                        local_grad += layers[l + 1].w[i][k] * layers[l + 1].local_grads[i];
-                    }
-                }
-                local_grad = (layers[l].act.der)(layers[l].outputs[j]) * local_grad;
-                layers[l].local_grads[j] = local_grad;
+            layer.batch_count += 1;
 
-                // set grads for each weight
-                if l == 0 {
-                    for k in 0..layers[l].inputs.len() {
-                        layers[l].grads[j][k] = layers[l].inputs[k] * local_grad;
-                    }
-                } else {
-                    for k in 0..layers[l - 1].outputs.len() {
-                        layers[l].grads[j][k] =
-                            (layers[l - 1].act.func)(layers[l - 1].outputs[k]) * local_grad;
+            if l > 0 {
+                // gradients w.r.t. the previous layer outputs, only through the weights
+                // connected to each of its neurons
+                let mut grads = vec![0.0; layer.w.cols()];
+                for (w_j, local_grad) in layer.w.iter_rows().zip(local_grads.iter()) {
+                    for (g_k, w_jk) in grads.iter_mut().zip(w_j.iter()) {
+                        *g_k += w_jk * local_grad;
                     }
                 }
+                local_grads = layers[l - 1].act.backward(&layers[l - 1].outputs, &grads);
             }
         }
     }
@@ -126,6 +164,7 @@ impl Loss {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::activator;
 
     #[test]
     fn test_mse_func() {
@@ -143,16 +182,72 @@ mod tests {
     fn test_mse() {
         let mut loss = Loss::square_err();
 
-        let l = loss.criterion(&vec![2.0, 1.0, 0.0], &vec![0.0, 1.0, 2.0]);
+        let l = loss.criterion(&[2.0, 1.0, 0.0], &[0.0, 1.0, 2.0]);
         assert_eq!(l, 4.0);
 
         loss.criterion(
-            &vec![34.0, 37.0, 44.0, 47.0, 48.0],
-            &vec![37.0, 40.0, 46.0, 44.0, 46.0],
+            &[34.0, 37.0, 44.0, 47.0, 48.0],
+            &[37.0, 40.0, 46.0, 44.0, 46.0],
         );
         assert_eq!(l, 4.0);
     }
 
+    #[test]
+    fn test_criterion_batch() {
+        let outputs = Matrix::from_rows(&[vec![2.0, 1.0], vec![0.0, 3.0]]);
+        let desired = Matrix::from_rows(&[vec![0.0, 1.0], vec![2.0, 3.0]]);
+        assert_eq!(Loss::square_err().criterion_batch(&outputs, &desired), 4.0);
+    }
+
+    #[test]
+    fn test_backward_accumulates() {
+        let mut net = mlp::Net::from_layers(vec![
+            mlp::Layer::new(2, 2, 1.0, activator::sigmoid()),
+            mlp::Layer::new(2, 1, 1.0, activator::linear()),
+        ]);
+        let mut loss = Loss::square_err();
+
+        let result = net.forward(&[0.5, 1.0]);
+        loss.criterion(&result, &[1.0]);
+        loss.backward(&mut net.layers);
+        let single = net.layers[1].grads.row(0).to_vec();
+        let single_b = net.layers[0].b_grads.clone();
+
+        loss.backward(&mut net.layers);
+        assert_eq!(net.layers[1].batch_count, 2);
+        assert_eq!(net.layers[1].grads[(0, 0)], 2.0 * single[0]);
+        assert_eq!(net.layers[1].grads[(0, 1)], 2.0 * single[1]);
+        assert_eq!(net.layers[0].b_grads[0], 2.0 * single_b[0]);
+    }
+
+    #[test]
+    fn test_cce_softmax_fused() {
+        let mut net = mlp::Net::from_layers(vec![
+            mlp::Layer::new(2, 3, 1.0, activator::sigmoid()),
+            mlp::Layer::new(3, 2, 1.0, activator::softmax()),
+        ]);
+        let mut loss = Loss::cce();
+
+        let result = net.forward(&[0.5, -1.0]);
+        let l = loss.criterion(&result, &[0.0, 1.0]);
+        assert!((l + result[1].ln()).abs() < 1e-12);
+        loss.backward(&mut net.layers);
+        assert!((net.layers[1].local_grads[0] - result[0]).abs() < 1e-12);
+        assert!((net.layers[1].local_grads[1] - (result[1] - 1.0)).abs() < 1e-12);
+
+        // unfused softmax jacobian gives the same gradients
+        let fused = net.layers[1].local_grads.clone();
+        let grads: Vec<f64> = result
+            .iter()
+            .zip([0.0, 1.0].iter())
+            .map(|(o, d)| (loss.der)(*o, *d))
+            .collect();
+        let unfused = net.layers[1].act.backward(&net.layers[1].outputs, &grads);
+        for (a, b) in fused.iter().zip(unfused.iter()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+    }
+
     #[test]
     fn test_bce_func() {
         println!("{}", (Loss::bce().func)(0.9, 0.0));
@@ -1,9 +1,13 @@
 pub mod activator;
 pub mod ga;
 pub mod loss;
+pub mod matrix;
+pub mod metrics;
 pub mod mlp;
 pub mod models;
+pub mod optim;
 pub mod swarm;
+pub mod trainer;
 pub mod utills;
 
 use std::error::Error;
@@ -16,6 +20,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     //models::wdbc::wdbc_30_15_1();
     //models::wdbc::wdbc_30_7_1();
     //models::wdbc::wdbc_30_15_7_1();
+    //models::wdbc::wdbc_30_15_1_nsga();
 
     // training code
 
@@ -25,10 +30,12 @@ fn main() -> Result<(), Box<dyn Error>> {
     flood::flood_8_4_1(0.0001, 0.01, "flood-8-4-1_3", true)?; // 3
     flood::flood_8_4_1(0.01, 0.01, "flood-8-4-1_4", false)?; // 4
     models::flood::flood_8_8_1(0.01, 0.01, "flood-8-8-1")?;
+    flood::flood_8_4_1_step_decay(0.01, 0.01, "flood-8-4-1_step")?;
     cross::cross_2_4_1(0.01, 0.01, "cross-2-4-1")?;
     cross::cross_2_4_1(0.01, 0.0, "cross-2-4-1_2")?;
     cross::cross_2_4_1(0.0001, 0.01, "cross-2-4-1_3")?;
     cross::cross_2_8_1(0.01, 0.01, "cross-2-8-1")?;
+    cross::cross_2_4_1_cosine(0.01, 0.01, "cross-2-4-1_cosine")?;
     */
 
     Ok(())
@@ -37,32 +44,16 @@ fn main() -> Result<(), Box<dyn Error>> {
 // temp code
 /*
 let dataset = utills::data::flood_dataset()?;
-let mut net = utills::io::load("models/flood-8-4-1/3.json")?;
-
-let mean = dataset.mean();
-let std = dataset.std();
-let st_dt = utills::data::standardization(&dataset, mean, std);
-
-let mut loss_mean = 0.0;
-for data in st_dt.get_datas() {
-    let result = un_standardization(net.forward(&data.inputs)[0], mean, std);
-    let desired = un_standardization(data.labels[0], mean, std);
-    println!("desired: {}, result: {:.3}, diff: {:.3}", desired, result, (desired-result).abs());
-    loss_mean += (result-desired).powi(2);
-}
-println!("standardization: {}", (loss_mean/st_dt.len() as f64).sqrt());
-
-let mut net = utills::io::load("models/flood-8-4-1/3.json")?;
-let min = dataset.min();
-let max = dataset.max();
-let st_dt = utills::data::minmax_norm(&dataset, min, max);
+// checkpoints keep the input scaler, raw water levels in and out
+let model = utills::io::load_model("models/flood-8-4-1/3.json")?;
+let net = model.to_net()?;
+let norm = model.normalization.unwrap_or_default();
 
+let result = norm.predict(&net, &dataset.inputs_matrix());
 let mut loss_mean = 0.0;
-for data in st_dt.get_datas() {
-    let result = net.forward(&data.inputs)[0] * (max - min) + min;
-    let desired = data.labels[0] * (max - min) + min;
-    //println!("desired: {}, result: {:.3}, diff: {:.3}", desired, result, (desired-result).abs());
-    loss_mean += (result-desired).abs();
+for (data, out) in dataset.get_datas().iter().zip(result.iter_rows()) {
+    println!("desired: {}, result: {:.3}, diff: {:.3}", data.labels[0], out[0], (data.labels[0]-out[0]).abs());
+    loss_mean += (out[0]-data.labels[0]).powi(2);
 }
-println!("min-max: {}", loss_mean);
+println!("rmse: {}", (loss_mean/dataset.len() as f64).sqrt());
 */
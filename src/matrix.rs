@@ -0,0 +1,122 @@
+//! Dense row-major matrix used for layer parameters and batches of samples.
+use serde::{Deserialize, Serialize};
+use std::ops::{Index, IndexMut};
+use std::slice::Chunks;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    data: Vec<f64>,
+}
+
+impl Matrix {
+    pub fn zeros(rows: usize, cols: usize) -> Matrix {
+        Matrix {
+            rows,
+            cols,
+            data: vec![0.0; rows * cols],
+        }
+    }
+
+    /// Create matrix from row-major `data`
+    pub fn from_vec(rows: usize, cols: usize, data: Vec<f64>) -> Matrix {
+        if data.len() != rows * cols {
+            panic!("data size is not equal to rows * cols");
+        }
+        Matrix { rows, cols, data }
+    }
+
+    pub fn from_rows(rows: &[Vec<f64>]) -> Matrix {
+        let cols = if rows.is_empty() { 0 } else { rows[0].len() };
+        let mut data: Vec<f64> = Vec::with_capacity(rows.len() * cols);
+        for r in rows {
+            if r.len() != cols {
+                panic!("every row must have the same size");
+            }
+            data.extend_from_slice(r);
+        }
+        Matrix {
+            rows: rows.len(),
+            cols,
+            data,
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn row(&self, j: usize) -> &[f64] {
+        &self.data[j * self.cols..(j + 1) * self.cols]
+    }
+
+    pub fn row_mut(&mut self, j: usize) -> &mut [f64] {
+        &mut self.data[j * self.cols..(j + 1) * self.cols]
+    }
+
+    pub fn iter_rows(&self) -> Chunks<'_, f64> {
+        // chunks panics on 0, a matrix without columns has no data to iterate anyway
+        self.data.chunks(self.cols.max(1))
+    }
+
+    pub fn as_slice(&self) -> &[f64] {
+        &self.data
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [f64] {
+        &mut self.data
+    }
+
+    pub fn fill(&mut self, v: f64) {
+        self.data.iter_mut().for_each(|x| *x = v);
+    }
+
+    pub fn to_rows(&self) -> Vec<Vec<f64>> {
+        self.iter_rows().map(|r| r.to_vec()).collect()
+    }
+}
+
+impl Index<(usize, usize)> for Matrix {
+    type Output = f64;
+
+    fn index(&self, (j, i): (usize, usize)) -> &f64 {
+        &self.data[j * self.cols + i]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Matrix {
+    fn index_mut(&mut self, (j, i): (usize, usize)) -> &mut f64 {
+        &mut self.data[j * self.cols + i]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row_major() {
+        let mut m = Matrix::from_rows(&[vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]);
+        assert_eq!(m.rows(), 2);
+        assert_eq!(m.cols(), 3);
+        assert_eq!(m.as_slice(), &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(m[(1, 0)], 4.0);
+        assert_eq!(m.row(1), &[4.0, 5.0, 6.0]);
+
+        m[(0, 2)] = 9.0;
+        m.row_mut(1)[1] = 7.0;
+        assert_eq!(m.to_rows(), vec![vec![1.0, 2.0, 9.0], vec![4.0, 7.0, 6.0]]);
+        assert_eq!(m.iter_rows().count(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_from_vec_wrong_size() {
+        Matrix::from_vec(2, 2, vec![1.0, 2.0, 3.0]);
+    }
+}
@@ -0,0 +1,329 @@
+//! Classification scores from class indices (see `decode`) or from scores of the
+//! positive class.
+use crate::matrix::Matrix;
+
+/// How per-class scores are combined into one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Average {
+    /// Unweighted mean over the classes
+    Macro,
+    /// Score of the counts summed over the classes
+    Micro,
+    /// Mean over the classes weighted by their number of samples
+    Weighted,
+    /// Score of this class only
+    Binary(usize),
+}
+
+/// Class of every row of `outputs`: `output > threshold` for a single output, the argmax
+/// otherwise. Works for one-hot or 0/1 labels too.
+pub fn decode(outputs: &Matrix, threshold: f64) -> Vec<usize> {
+    outputs
+        .iter_rows()
+        .map(|r| {
+            if r.len() == 1 {
+                (r[0] > threshold) as usize
+            } else {
+                r.iter()
+                    .enumerate()
+                    .fold((0, f64::MIN), |m, (i, &v)| if v > m.1 { (i, v) } else { m })
+                    .0
+            }
+        })
+        .collect()
+}
+
+/// True/false positives and negatives of one class against the rest
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Counts {
+    pub tp: usize,
+    pub fp: usize,
+    pub fn_: usize,
+    pub tn: usize,
+}
+
+fn ratio(a: usize, b: usize) -> f64 {
+    if b == 0 {
+        0.0
+    } else {
+        a as f64 / b as f64
+    }
+}
+
+impl Counts {
+    pub fn precision(&self) -> f64 {
+        ratio(self.tp, self.tp + self.fp)
+    }
+
+    pub fn recall(&self) -> f64 {
+        ratio(self.tp, self.tp + self.fn_)
+    }
+
+    pub fn specificity(&self) -> f64 {
+        ratio(self.tn, self.tn + self.fp)
+    }
+
+    /// Weighted harmonic mean of precision and recall, recall counts `beta` times as much
+    pub fn f_beta(&self, beta: f64) -> f64 {
+        let (p, r) = (self.precision(), self.recall());
+        let b2 = beta * beta;
+        if p + r == 0.0 {
+            0.0
+        } else {
+            (1.0 + b2) * p * r / (b2 * p + r)
+        }
+    }
+
+    fn add(self, other: Counts) -> Counts {
+        Counts {
+            tp: self.tp + other.tp,
+            fp: self.fp + other.fp,
+            fn_: self.fn_ + other.fn_,
+            tn: self.tn + other.tn,
+        }
+    }
+}
+
+/// `Counts` of every class
+pub fn class_counts(desired: &[usize], predicted: &[usize], n_classes: usize) -> Vec<Counts> {
+    if desired.len() != predicted.len() {
+        panic!("predicted size is not equal to desired size");
+    }
+    (0..n_classes)
+        .map(|c| {
+            let mut counts = Counts::default();
+            for (&d, &p) in desired.iter().zip(predicted.iter()) {
+                match (d == c, p == c) {
+                    (true, true) => counts.tp += 1,
+                    (false, true) => counts.fp += 1,
+                    (true, false) => counts.fn_ += 1,
+                    (false, false) => counts.tn += 1,
+                }
+            }
+            counts
+        })
+        .collect()
+}
+
+/// Combine `score` of every class of `counts` with `avg`
+pub fn average(counts: &[Counts], avg: Average, score: impl Fn(&Counts) -> f64) -> f64 {
+    match avg {
+        Average::Binary(c) => score(&counts[c]),
+        Average::Micro => score(&counts.iter().fold(Counts::default(), |s, c| s.add(*c))),
+        Average::Macro => counts.iter().map(&score).sum::<f64>() / counts.len() as f64,
+        Average::Weighted => {
+            let total: usize = counts.iter().map(|c| c.tp + c.fn_).sum();
+            counts
+                .iter()
+                .map(|c| score(c) * ratio(c.tp + c.fn_, total))
+                .sum()
+        }
+    }
+}
+
+pub fn accuracy(desired: &[usize], predicted: &[usize]) -> f64 {
+    let correct = desired
+        .iter()
+        .zip(predicted.iter())
+        .filter(|(d, p)| d == p)
+        .count();
+    ratio(correct, desired.len())
+}
+
+pub fn precision(desired: &[usize], predicted: &[usize], n_classes: usize, avg: Average) -> f64 {
+    average(
+        &class_counts(desired, predicted, n_classes),
+        avg,
+        Counts::precision,
+    )
+}
+
+pub fn recall(desired: &[usize], predicted: &[usize], n_classes: usize, avg: Average) -> f64 {
+    average(
+        &class_counts(desired, predicted, n_classes),
+        avg,
+        Counts::recall,
+    )
+}
+
+pub fn specificity(desired: &[usize], predicted: &[usize], n_classes: usize, avg: Average) -> f64 {
+    average(
+        &class_counts(desired, predicted, n_classes),
+        avg,
+        Counts::specificity,
+    )
+}
+
+pub fn f1(desired: &[usize], predicted: &[usize], n_classes: usize, avg: Average) -> f64 {
+    average(&class_counts(desired, predicted, n_classes), avg, |c| {
+        c.f_beta(1.0)
+    })
+}
+
+/// Mean recall of the classes, accuracy that is not fooled by imbalanced classes
+pub fn balanced_accuracy(desired: &[usize], predicted: &[usize], n_classes: usize) -> f64 {
+    recall(desired, predicted, n_classes, Average::Macro)
+}
+
+/// Matthews correlation coefficient, generalized to any number of classes
+pub fn mcc(desired: &[usize], predicted: &[usize], n_classes: usize) -> f64 {
+    mcc_counts(&class_counts(desired, predicted, n_classes))
+}
+
+/// `mcc` from the `Counts` of every class
+pub fn mcc_counts(counts: &[Counts]) -> f64 {
+    let s: f64 = counts.iter().map(|c| (c.tp + c.fn_) as f64).sum();
+    let c: f64 = counts.iter().map(|c| c.tp as f64).sum();
+    let t: Vec<f64> = counts.iter().map(|c| (c.tp + c.fn_) as f64).collect();
+    let p: Vec<f64> = counts.iter().map(|c| (c.tp + c.fp) as f64).collect();
+    let pt: f64 = t.iter().zip(p.iter()).map(|(t, p)| t * p).sum();
+    let pp: f64 = p.iter().map(|p| p * p).sum();
+    let tt: f64 = t.iter().map(|t| t * t).sum();
+    let denom = ((s * s - pp) * (s * s - tt)).sqrt();
+    if denom == 0.0 {
+        0.0
+    } else {
+        (c * s - pt) / denom
+    }
+}
+
+/// Area under the ROC curve of `scores` for samples where `positive` is true, the
+/// probability that a random positive scores higher than a random negative
+pub fn roc_auc(positive: &[bool], scores: &[f64]) -> f64 {
+    if positive.len() != scores.len() {
+        panic!("scores size is not equal to desired size");
+    }
+    let mut idx: Vec<usize> = (0..scores.len()).collect();
+    idx.sort_by(|&a, &b| scores[a].partial_cmp(&scores[b]).unwrap());
+
+    // sum of the ranks of the positives, tied scores share their mean rank
+    let mut rank_sum = 0.0;
+    let mut i = 0;
+    while i < idx.len() {
+        let j = i + idx[i..]
+            .iter()
+            .take_while(|&&k| scores[k] == scores[idx[i]])
+            .count();
+        let rank = (i + j + 1) as f64 / 2.0;
+        rank_sum += rank * idx[i..j].iter().filter(|&&k| positive[k]).count() as f64;
+        i = j;
+    }
+    let n_pos = positive.iter().filter(|p| **p).count() as f64;
+    let n_neg = positive.len() as f64 - n_pos;
+    (rank_sum - n_pos * (n_pos + 1.0) / 2.0) / (n_pos * n_neg)
+}
+
+/// Area under the precision-recall curve as average precision: the precision at each
+/// threshold weighted by the recall gained there
+pub fn pr_auc(positive: &[bool], scores: &[f64]) -> f64 {
+    if positive.len() != scores.len() {
+        panic!("scores size is not equal to desired size");
+    }
+    let mut idx: Vec<usize> = (0..scores.len()).collect();
+    idx.sort_by(|&a, &b| scores[b].partial_cmp(&scores[a]).unwrap());
+    let n_pos = positive.iter().filter(|p| **p).count() as f64;
+
+    let (mut tp, mut seen) = (0.0, 0.0);
+    let mut prev_recall = 0.0;
+    let mut ap = 0.0;
+    let mut i = 0;
+    while i < idx.len() {
+        // every sample with the same score is on the same side of a threshold
+        let j = i + idx[i..]
+            .iter()
+            .take_while(|&&k| scores[k] == scores[idx[i]])
+            .count();
+        tp += idx[i..j].iter().filter(|&&k| positive[k]).count() as f64;
+        seen += (j - i) as f64;
+        let recall = tp / n_pos;
+        ap += (recall - prev_recall) * tp / seen;
+        prev_recall = recall;
+        i = j;
+    }
+    ap
+}
+
+/// Mean cross entropy of probabilities `outputs`, binary for a single output
+pub fn log_loss(desired: &Matrix, outputs: &Matrix) -> f64 {
+    if desired.rows() != outputs.rows() || desired.cols() != outputs.cols() {
+        panic!("outputs size is not equal to desired size");
+    }
+    let eps = 1e-15;
+    let total: f64 = desired
+        .iter_rows()
+        .zip(outputs.iter_rows())
+        .map(|(d, o)| {
+            if d.len() == 1 {
+                let p = o[0].clamp(eps, 1.0 - eps);
+                -(d[0] * p.ln() + (1.0 - d[0]) * (1.0 - p).ln())
+            } else {
+                -d.iter()
+                    .zip(o.iter())
+                    .map(|(d, o)| d * o.clamp(eps, 1.0).ln())
+                    .sum::<f64>()
+            }
+        })
+        .sum();
+    total / desired.rows() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_averaged_scores() {
+        let desired = [0, 1, 2, 0, 1, 2];
+        let predicted = [0, 2, 1, 0, 0, 1];
+        assert_eq!(accuracy(&desired, &predicted), 2.0 / 6.0);
+        assert!((precision(&desired, &predicted, 3, Average::Macro) - 2.0 / 9.0).abs() < 1e-12);
+        assert_eq!(
+            precision(&desired, &predicted, 3, Average::Micro),
+            2.0 / 6.0
+        );
+        assert_eq!(recall(&desired, &predicted, 3, Average::Binary(0)), 1.0);
+        assert!((f1(&desired, &predicted, 3, Average::Macro) - 0.26666666666666666).abs() < 1e-12);
+        assert!(
+            (f1(&desired, &predicted, 3, Average::Weighted) - 0.26666666666666666).abs() < 1e-12
+        );
+        assert_eq!(
+            specificity(&desired, &predicted, 3, Average::Binary(0)),
+            0.75
+        );
+        assert_eq!(balanced_accuracy(&desired, &predicted, 3), 1.0 / 3.0);
+    }
+
+    #[test]
+    fn test_mcc() {
+        assert_eq!(mcc(&[0, 1, 0, 1], &[0, 1, 0, 1], 2), 1.0);
+        assert_eq!(mcc(&[0, 1, 0, 1], &[1, 0, 1, 0], 2), -1.0);
+        assert_eq!(mcc(&[0, 1, 0, 1], &[0, 0, 0, 0], 2), 0.0);
+        let v = mcc(&[1, 1, 1, 0], &[1, 0, 1, 1], 2);
+        assert!((v + 1.0 / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_auc() {
+        let positive = [false, false, true, true];
+        let scores = [0.1, 0.4, 0.35, 0.8];
+        assert_eq!(roc_auc(&positive, &scores), 0.75);
+        assert!((pr_auc(&positive, &scores) - 0.8333333333333333).abs() < 1e-12);
+        // ties count half
+        assert_eq!(roc_auc(&[false, true], &[0.5, 0.5]), 0.5);
+    }
+
+    #[test]
+    fn test_decode_and_log_loss() {
+        let outputs = Matrix::from_rows(&[vec![0.2, 0.8], vec![0.6, 0.4]]);
+        assert_eq!(decode(&outputs, 0.5), vec![1, 0]);
+        let single = Matrix::from_rows(&[vec![0.7], vec![0.3]]);
+        assert_eq!(decode(&single, 0.5), vec![1, 0]);
+        assert_eq!(decode(&single, 0.8), vec![0, 0]);
+
+        let desired = Matrix::from_rows(&[vec![1.0], vec![0.0]]);
+        assert!((log_loss(&desired, &single) + 0.7f64.ln()).abs() < 1e-12);
+        let desired = Matrix::from_rows(&[vec![0.0, 1.0], vec![1.0, 0.0]]);
+        let expected = -(0.8f64.ln() + 0.6f64.ln()) / 2.0;
+        assert!((log_loss(&desired, &outputs) - expected).abs() < 1e-12);
+    }
+}
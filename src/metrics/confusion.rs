@@ -0,0 +1,204 @@
+//! Confusion matrix for any number of classes.
+use super::classification::{self, decode, Average, Counts};
+use crate::matrix::Matrix;
+
+/// What `ConfusionMatrix::normalize` divides every count by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalize {
+    /// Samples of the desired class, the diagonal is the recall
+    Row,
+    /// Samples of the predicted class, the diagonal is the precision
+    Column,
+    /// Every sample
+    All,
+}
+
+/// `counts[d][p]` is the number of samples of class `d` predicted as class `p`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfusionMatrix {
+    pub counts: Vec<Vec<usize>>,
+}
+
+impl ConfusionMatrix {
+    pub fn new(n_classes: usize) -> ConfusionMatrix {
+        ConfusionMatrix {
+            counts: vec![vec![0; n_classes]; n_classes],
+        }
+    }
+
+    pub fn from_classes(
+        desired: &[usize],
+        predicted: &[usize],
+        n_classes: usize,
+    ) -> ConfusionMatrix {
+        let mut m = ConfusionMatrix::new(n_classes);
+        for (&d, &p) in desired.iter().zip(predicted.iter()) {
+            m.add(d, p);
+        }
+        m
+    }
+
+    /// Decode labels and network outputs (one sample per row) with
+    /// `classification::decode`, a single output gives two classes
+    pub fn from_outputs(desired: &Matrix, outputs: &Matrix, threshold: f64) -> ConfusionMatrix {
+        if desired.rows() != outputs.rows() || desired.cols() != outputs.cols() {
+            panic!("outputs size is not equal to desired size");
+        }
+        ConfusionMatrix::from_classes(
+            &decode(desired, 0.5),
+            &decode(outputs, threshold),
+            desired.cols().max(2),
+        )
+    }
+
+    pub fn add(&mut self, desired: usize, predicted: usize) {
+        self.counts[desired][predicted] += 1;
+    }
+
+    /// Add the counts of `other`, e.g. to sum the folds of a cross-validation
+    pub fn merge(&mut self, other: &ConfusionMatrix) {
+        if other.n_classes() != self.n_classes() {
+            panic!("confusion matrices must have the same number of classes");
+        }
+        for (row, other_row) in self.counts.iter_mut().zip(other.counts.iter()) {
+            for (c, o) in row.iter_mut().zip(other_row.iter()) {
+                *c += o;
+            }
+        }
+    }
+
+    pub fn n_classes(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().flatten().sum()
+    }
+
+    /// `Counts` of every class against the rest
+    pub fn class_counts(&self) -> Vec<Counts> {
+        let total = self.total();
+        (0..self.n_classes())
+            .map(|c| {
+                let tp = self.counts[c][c];
+                let fn_ = self.counts[c].iter().sum::<usize>() - tp;
+                let fp = self.counts.iter().map(|r| r[c]).sum::<usize>() - tp;
+                Counts {
+                    tp,
+                    fp,
+                    fn_,
+                    tn: total - tp - fn_ - fp,
+                }
+            })
+            .collect()
+    }
+
+    pub fn accuracy(&self) -> f64 {
+        let correct: usize = (0..self.n_classes()).map(|c| self.counts[c][c]).sum();
+        correct as f64 / self.total() as f64
+    }
+
+    pub fn precision(&self, avg: Average) -> f64 {
+        classification::average(&self.class_counts(), avg, Counts::precision)
+    }
+
+    pub fn recall(&self, avg: Average) -> f64 {
+        classification::average(&self.class_counts(), avg, Counts::recall)
+    }
+
+    pub fn specificity(&self, avg: Average) -> f64 {
+        classification::average(&self.class_counts(), avg, Counts::specificity)
+    }
+
+    pub fn f1(&self, avg: Average) -> f64 {
+        classification::average(&self.class_counts(), avg, |c| c.f_beta(1.0))
+    }
+
+    pub fn balanced_accuracy(&self) -> f64 {
+        self.recall(Average::Macro)
+    }
+
+    pub fn mcc(&self) -> f64 {
+        classification::mcc_counts(&self.class_counts())
+    }
+
+    /// Counts divided by their row, column or grand total, 0 where the total is 0
+    pub fn normalize(&self, by: Normalize) -> Vec<Vec<f64>> {
+        let n = self.n_classes();
+        let total = self.total();
+        let col_sums: Vec<usize> = (0..n)
+            .map(|p| self.counts.iter().map(|r| r[p]).sum())
+            .collect();
+        self.counts
+            .iter()
+            .map(|row| {
+                let row_sum: usize = row.iter().sum();
+                row.iter()
+                    .enumerate()
+                    .map(|(p, &c)| {
+                        let div = match by {
+                            Normalize::Row => row_sum,
+                            Normalize::Column => col_sums[p],
+                            Normalize::All => total,
+                        };
+                        if div == 0 {
+                            0.0
+                        } else {
+                            c as f64 / div as f64
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix() -> ConfusionMatrix {
+        ConfusionMatrix::from_classes(&[0, 1, 2, 0, 1, 2], &[0, 2, 1, 0, 0, 1], 3)
+    }
+
+    #[test]
+    fn test_metrics_match_classification() {
+        let (desired, predicted) = ([0, 1, 2, 0, 1, 2], [0, 2, 1, 0, 0, 1]);
+        let m = matrix();
+        assert_eq!(m.counts, vec![vec![2, 0, 0], vec![1, 0, 1], vec![0, 2, 0]]);
+        assert_eq!(m.accuracy(), classification::accuracy(&desired, &predicted));
+        assert_eq!(
+            m.class_counts(),
+            classification::class_counts(&desired, &predicted, 3)
+        );
+        for avg in [Average::Macro, Average::Micro, Average::Weighted] {
+            assert_eq!(m.f1(avg), classification::f1(&desired, &predicted, 3, avg));
+        }
+        assert_eq!(m.mcc(), classification::mcc(&desired, &predicted, 3));
+    }
+
+    #[test]
+    fn test_normalize_and_merge() {
+        let mut m = matrix();
+        let rows = m.normalize(Normalize::Row);
+        assert_eq!(rows[1], vec![0.5, 0.0, 0.5]);
+        let cols = m.normalize(Normalize::Column);
+        assert_eq!(cols[0][0], 2.0 / 3.0);
+        assert_eq!(cols[2][2], 0.0);
+
+        m.merge(&matrix());
+        assert_eq!(m.total(), 12);
+        assert_eq!(m.counts[0][0], 4);
+    }
+
+    #[test]
+    fn test_from_outputs() {
+        let desired = Matrix::from_rows(&[vec![1.0], vec![0.0], vec![1.0]]);
+        let outputs = Matrix::from_rows(&[vec![0.9], vec![0.6], vec![0.2]]);
+        let m = ConfusionMatrix::from_outputs(&desired, &outputs, 0.5);
+        // rows are desired classes, columns predicted ones
+        assert_eq!(m.counts, vec![vec![0, 1], vec![1, 1]]);
+        let m = ConfusionMatrix::from_outputs(&desired, &outputs, 0.7);
+        assert_eq!(m.counts, vec![vec![1, 0], vec![1, 1]]);
+    }
+}
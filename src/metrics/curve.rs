@@ -0,0 +1,214 @@
+//! Sweep of the decision threshold of a binary classifier: ROC and precision-recall
+//! curves and the choice of a threshold.
+use super::classification::{decode, Counts};
+use crate::matrix::Matrix;
+
+/// How `Curve::best` scores a threshold
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Criterion {
+    /// Youden's J, true positive rate minus false positive rate
+    Youden,
+    /// F-beta score, recall counts `beta` times as much as precision
+    FBeta(f64),
+    /// Lowest total cost, `cost[d][p]` is the cost of a sample of class `d` predicted
+    /// as class `p` (class 1 is the positive class), like `ConfusionMatrix` counts
+    Cost([[f64; 2]; 2]),
+}
+
+/// Counts at every threshold between two distinct scores, a sample is predicted
+/// positive when its score is above the threshold (as in `decode`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve {
+    /// Decreasing, from nothing to everything predicted positive
+    pub thresholds: Vec<f64>,
+    pub counts: Vec<Counts>,
+}
+
+impl Curve {
+    pub fn new(positive: &[bool], scores: &[f64]) -> Curve {
+        if positive.len() != scores.len() {
+            panic!("scores size is not equal to desired size");
+        }
+        if scores.iter().any(|s| !s.is_finite()) {
+            panic!("scores must be finite");
+        }
+        let mut idx: Vec<usize> = (0..scores.len()).collect();
+        idx.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+        let n_pos = positive.iter().filter(|p| **p).count();
+        let n_neg = positive.len() - n_pos;
+
+        let mut counts = Counts {
+            tp: 0,
+            fp: 0,
+            fn_: n_pos,
+            tn: n_neg,
+        };
+        let mut curve = Curve {
+            thresholds: vec![],
+            counts: vec![],
+        };
+        let mut i = 0;
+        while i < idx.len() {
+            let score = scores[idx[i]];
+            // halfway to the next score, so samples scored in between are split evenly
+            curve.thresholds.push(if i == 0 {
+                score
+            } else {
+                (scores[idx[i - 1]] + score) / 2.0
+            });
+            curve.counts.push(counts);
+            while i < idx.len() && scores[idx[i]] == score {
+                if positive[idx[i]] {
+                    counts.tp += 1;
+                    counts.fn_ -= 1;
+                } else {
+                    counts.fp += 1;
+                    counts.tn -= 1;
+                }
+                i += 1;
+            }
+        }
+        curve.thresholds.push(f64::NEG_INFINITY);
+        curve.counts.push(counts);
+        curve
+    }
+
+    /// Curve of the scores of `class`: its output, or the single output for class 1.
+    /// `desired` and `outputs` have one sample per row.
+    pub fn from_outputs(desired: &Matrix, outputs: &Matrix, class: usize) -> Curve {
+        if desired.rows() != outputs.rows() || desired.cols() != outputs.cols() {
+            panic!("outputs size is not equal to desired size");
+        }
+        let positive: Vec<bool> = decode(desired, 0.5).iter().map(|&c| c == class).collect();
+        let scores: Vec<f64> = if outputs.cols() == 1 {
+            if class != 1 {
+                panic!("the single output is the score of class 1");
+            }
+            outputs.iter_rows().map(|r| r[0]).collect()
+        } else {
+            outputs.iter_rows().map(|r| r[class]).collect()
+        };
+        Curve::new(&positive, &scores)
+    }
+
+    /// (false positive rate, true positive rate) at every threshold
+    pub fn roc(&self) -> Vec<(f64, f64)> {
+        self.counts
+            .iter()
+            .map(|c| (c.fp as f64 / (c.fp + c.tn).max(1) as f64, c.recall()))
+            .collect()
+    }
+
+    /// Area under `roc` by trapezoids, the same as `classification::roc_auc`
+    pub fn roc_auc(&self) -> f64 {
+        self.roc()
+            .windows(2)
+            .map(|w| (w[1].0 - w[0].0) * (w[1].1 + w[0].1) / 2.0)
+            .sum()
+    }
+
+    /// (recall, precision) at every threshold predicting some sample positive
+    pub fn pr(&self) -> Vec<(f64, f64)> {
+        self.counts
+            .iter()
+            .filter(|c| c.tp + c.fp > 0)
+            .map(|c| (c.recall(), c.precision()))
+            .collect()
+    }
+
+    /// Threshold with the best `criterion` and its score (the cost for `Cost`), the
+    /// highest such threshold on ties
+    pub fn best(&self, criterion: Criterion) -> (f64, f64) {
+        let score = |c: &Counts| match criterion {
+            Criterion::Youden => c.recall() + c.specificity() - 1.0,
+            Criterion::FBeta(beta) => c.f_beta(beta),
+            // negated so that higher is better
+            Criterion::Cost(cost) => {
+                -(c.tn as f64 * cost[0][0]
+                    + c.fp as f64 * cost[0][1]
+                    + c.fn_ as f64 * cost[1][0]
+                    + c.tp as f64 * cost[1][1])
+            }
+        };
+        let (mut best, mut best_score) = (0, f64::MIN);
+        for (i, c) in self.counts.iter().enumerate() {
+            if score(c) > best_score {
+                best = i;
+                best_score = score(c);
+            }
+        }
+        if let Criterion::Cost(_) = criterion {
+            best_score = -best_score;
+        }
+        (self.thresholds[best], best_score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::classification::roc_auc;
+
+    fn curve() -> Curve {
+        Curve::new(
+            &[false, false, true, true, false, true],
+            &[0.0, 0.5, 0.375, 1.0, 0.75, 0.75],
+        )
+    }
+
+    #[test]
+    fn test_curve() {
+        let c = curve();
+        assert_eq!(
+            c.thresholds,
+            vec![1.0, 0.875, 0.625, 0.4375, 0.1875, f64::NEG_INFINITY]
+        );
+        let roc = c.roc();
+        assert_eq!(roc[0], (0.0, 0.0));
+        assert_eq!(roc[2], (1.0 / 3.0, 2.0 / 3.0));
+        assert_eq!(roc[5], (1.0, 1.0));
+        let auc = roc_auc(
+            &[false, false, true, true, false, true],
+            &[0.0, 0.5, 0.375, 1.0, 0.75, 0.75],
+        );
+        assert!((c.roc_auc() - auc).abs() < 1e-12);
+        assert_eq!(c.pr()[0], (1.0 / 3.0, 1.0));
+        assert_eq!(c.pr().len(), 5);
+    }
+
+    #[test]
+    fn test_best() {
+        let c = curve();
+        let (t, j) = c.best(Criterion::Youden);
+        assert_eq!(t, 0.875);
+        assert!((j - 1.0 / 3.0).abs() < 1e-12);
+        let (t, f) = c.best(Criterion::FBeta(1.0));
+        assert_eq!(t, 0.1875);
+        assert!((f - 0.75).abs() < 1e-12);
+        assert_eq!(
+            c.best(Criterion::Cost([[0.0, 1.0], [1.0, 0.0]])),
+            (0.875, 2.0)
+        );
+        // a false negative costs 10 false positives: no positive is missed
+        assert_eq!(
+            c.best(Criterion::Cost([[0.0, 1.0], [10.0, 0.0]])),
+            (0.1875, 2.0)
+        );
+    }
+
+    #[test]
+    fn test_from_outputs() {
+        let desired = Matrix::from_rows(&[vec![1.0, 0.0], vec![0.0, 1.0]]);
+        let outputs = Matrix::from_rows(&[vec![0.7, 0.3], vec![0.4, 0.6]]);
+        let c = Curve::from_outputs(&desired, &outputs, 0);
+        assert_eq!(c.thresholds[..2], [0.7, 0.55]);
+        assert_eq!(c.counts[1].tp, 1);
+        assert_eq!(c.counts[1].fp, 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_nan_score() {
+        Curve::new(&[true, false], &[0.5, f64::NAN]);
+    }
+}
@@ -0,0 +1,90 @@
+//! Classification and regression scores, and their mean and standard deviation across
+//! cross-validation folds.
+pub mod classification;
+pub mod confusion;
+pub mod curve;
+pub mod regression;
+
+use std::fmt;
+
+/// Mean and sample standard deviation of `values`, e.g. one score of every fold
+pub fn mean_std(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    if values.is_empty() {
+        return (f64::NAN, f64::NAN);
+    }
+    let mean = values.iter().sum::<f64>() / n;
+    if values.len() == 1 {
+        return (mean, 0.0);
+    }
+    let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (mean, var.sqrt())
+}
+
+/// Named scores collected fold by fold
+#[derive(Debug, Clone, Default)]
+pub struct FoldScores {
+    pub scores: Vec<(String, Vec<f64>)>,
+}
+
+impl FoldScores {
+    pub fn new() -> FoldScores {
+        FoldScores::default()
+    }
+
+    /// Add the score `name` of the next fold
+    pub fn push(&mut self, name: &str, value: f64) {
+        match self.scores.iter_mut().find(|(n, _)| n == name) {
+            Some((_, values)) => values.push(value),
+            None => self.scores.push((name.to_string(), vec![value])),
+        }
+    }
+
+    /// Every fold value of `name`
+    pub fn get(&self, name: &str) -> Option<&[f64]> {
+        self.scores
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, values)| values.as_slice())
+    }
+
+    /// (name, mean, std) of every score
+    pub fn summary(&self) -> Vec<(String, f64, f64)> {
+        self.scores
+            .iter()
+            .map(|(name, values)| {
+                let (mean, std) = mean_std(values);
+                (name.clone(), mean, std)
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for FoldScores {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, mean, std) in self.summary() {
+            writeln!(f, "{:<20} {:.6} ± {:.6}", name, mean, std)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_scores() {
+        let mut scores = FoldScores::new();
+        for v in [1.0, 2.0, 3.0] {
+            scores.push("mae", v);
+            scores.push("r2", 0.5);
+        }
+        assert_eq!(scores.get("mae").unwrap(), &[1.0, 2.0, 3.0]);
+        let summary = scores.summary();
+        assert_eq!(summary[0], ("mae".to_string(), 2.0, 1.0));
+        assert_eq!(summary[1], ("r2".to_string(), 0.5, 0.0));
+        assert!(format!("{}", scores).contains("mae"));
+        assert_eq!(mean_std(&[4.0]), (4.0, 0.0));
+    }
+}
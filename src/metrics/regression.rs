@@ -0,0 +1,145 @@
+//! Regression scores over every output of every sample (one sample per row).
+use crate::matrix::Matrix;
+
+fn check(desired: &Matrix, outputs: &Matrix) {
+    if desired.rows() != outputs.rows() || desired.cols() != outputs.cols() {
+        panic!("outputs size is not equal to desired size");
+    }
+}
+
+fn pairs<'a>(desired: &'a Matrix, outputs: &'a Matrix) -> impl Iterator<Item = (f64, f64)> + 'a {
+    check(desired, outputs);
+    desired
+        .as_slice()
+        .iter()
+        .copied()
+        .zip(outputs.as_slice().iter().copied())
+}
+
+fn column(m: &Matrix, i: usize) -> Vec<f64> {
+    m.iter_rows().map(|r| r[i]).collect()
+}
+
+fn mean(v: &[f64]) -> f64 {
+    v.iter().sum::<f64>() / v.len() as f64
+}
+
+/// Mean squared error
+pub fn mse(desired: &Matrix, outputs: &Matrix) -> f64 {
+    let n = desired.as_slice().len() as f64;
+    pairs(desired, outputs)
+        .map(|(d, o)| (d - o).powi(2))
+        .sum::<f64>()
+        / n
+}
+
+/// Root mean squared error
+pub fn rmse(desired: &Matrix, outputs: &Matrix) -> f64 {
+    mse(desired, outputs).sqrt()
+}
+
+/// Mean absolute error
+pub fn mae(desired: &Matrix, outputs: &Matrix) -> f64 {
+    let n = desired.as_slice().len() as f64;
+    pairs(desired, outputs)
+        .map(|(d, o)| (d - o).abs())
+        .sum::<f64>()
+        / n
+}
+
+/// Mean absolute percentage error in %, samples with a desired value of 0 are skipped
+pub fn mape(desired: &Matrix, outputs: &Matrix) -> f64 {
+    let errors: Vec<f64> = pairs(desired, outputs)
+        .filter(|(d, _)| *d != 0.0)
+        .map(|(d, o)| ((d - o) / d).abs())
+        .collect();
+    100.0 * mean(&errors)
+}
+
+/// Coefficient of determination, averaged over the outputs. An output with a constant
+/// desired value (e.g. a single sample) scores 1 when predicted exactly, else 0.
+pub fn r2(desired: &Matrix, outputs: &Matrix) -> f64 {
+    check(desired, outputs);
+    let scores: Vec<f64> = (0..desired.cols())
+        .map(|i| {
+            let (d, o) = (column(desired, i), column(outputs, i));
+            let m = mean(&d);
+            let ss_res: f64 = d.iter().zip(o.iter()).map(|(d, o)| (d - o).powi(2)).sum();
+            let ss_tot: f64 = d.iter().map(|d| (d - m).powi(2)).sum();
+            if ss_tot == 0.0 {
+                return if ss_res == 0.0 { 1.0 } else { 0.0 };
+            }
+            1.0 - ss_res / ss_tot
+        })
+        .collect();
+    mean(&scores)
+}
+
+/// 1 - Var(desired - outputs) / Var(desired), averaged over the outputs. Unlike `r2` a
+/// constant bias of the outputs is not penalized. A constant desired value scores 1
+/// when the residual is constant too, else 0.
+pub fn explained_variance(desired: &Matrix, outputs: &Matrix) -> f64 {
+    check(desired, outputs);
+    let var = |v: &[f64]| {
+        let m = mean(v);
+        v.iter().map(|x| (x - m).powi(2)).sum::<f64>() / v.len() as f64
+    };
+    let scores: Vec<f64> = (0..desired.cols())
+        .map(|i| {
+            let (d, o) = (column(desired, i), column(outputs, i));
+            let residual: Vec<f64> = d.iter().zip(o.iter()).map(|(d, o)| d - o).collect();
+            let (var_res, var_d) = (var(&residual), var(&d));
+            if var_d == 0.0 {
+                return if var_res == 0.0 { 1.0 } else { 0.0 };
+            }
+            1.0 - var_res / var_d
+        })
+        .collect();
+    mean(&scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn col(v: &[f64]) -> Matrix {
+        Matrix::from_vec(v.len(), 1, v.to_vec())
+    }
+
+    #[test]
+    fn test_errors() {
+        let d = col(&[3.0, -0.5, 2.0, 7.0]);
+        let o = col(&[2.5, 0.0, 2.0, 8.0]);
+        assert_eq!(mse(&d, &o), 0.375);
+        assert_eq!(rmse(&d, &o), 0.375f64.sqrt());
+        assert_eq!(mae(&d, &o), 0.5);
+        assert!((r2(&d, &o) - 0.9486081370449679).abs() < 1e-12);
+        assert!((explained_variance(&d, &o) - 0.9571734475374732).abs() < 1e-12);
+
+        let d = col(&[1.0, 2.0, 0.0]);
+        let o = col(&[1.5, 1.0, 3.0]);
+        assert_eq!(mape(&d, &o), 50.0);
+    }
+
+    #[test]
+    fn test_bias() {
+        let d = col(&[1.0, 2.0, 3.0]);
+        let o = col(&[2.0, 3.0, 4.0]);
+        assert_eq!(explained_variance(&d, &o), 1.0);
+        assert!(r2(&d, &o) < 1.0);
+    }
+
+    #[test]
+    fn test_constant_desired() {
+        // one validation sample, as with leave-one-out
+        let d = col(&[2.0]);
+        assert_eq!(r2(&d, &col(&[2.0])), 1.0);
+        assert_eq!(r2(&d, &col(&[3.0])), 0.0);
+        assert_eq!(explained_variance(&d, &col(&[3.0])), 1.0);
+
+        let d = col(&[2.0, 2.0]);
+        assert_eq!(r2(&d, &col(&[2.0, 2.0])), 1.0);
+        assert_eq!(r2(&d, &col(&[1.0, 2.0])), 0.0);
+        assert_eq!(explained_variance(&d, &col(&[1.0, 2.0])), 0.0);
+    }
+}
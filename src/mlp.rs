@@ -1,15 +1,17 @@
 use crate::activator;
+use crate::matrix::Matrix;
+use crate::optim::Optimizer;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Layer {
     pub inputs: Vec<f64>,
     pub outputs: Vec<f64>, // need to save this for backward pass
-    pub w: Vec<Vec<f64>>,
+    pub w: Matrix,         // output_features x input_features
     pub b: Vec<f64>,
-    pub grads: Vec<Vec<f64>>,
-    pub w_prev_changes: Vec<Vec<f64>>,
+    pub grads: Matrix,
     pub local_grads: Vec<f64>,
-    pub b_prev_changes: Vec<f64>,
+    pub b_grads: Vec<f64>,
+    pub batch_count: usize, // samples accumulated in grads since the last update
     pub act: activator::ActivationContainer,
 }
 
@@ -20,99 +22,93 @@ impl Layer {
         bias: f64,
         act: activator::ActivationContainer,
     ) -> Layer {
-        // initialize weights matrix
-        let mut weights: Vec<Vec<f64>> = vec![];
-        let mut inputs: Vec<f64> = vec![];
-        let mut outputs: Vec<f64> = vec![];
-        let mut grads: Vec<Vec<f64>> = vec![];
-        let mut local_grads: Vec<f64> = vec![];
-        let mut w_prev_changes: Vec<Vec<f64>> = vec![];
-        let mut b_prev_changes: Vec<f64> = vec![];
-        let mut b: Vec<f64> = vec![];
-
-        for _ in 0..output_features {
-            outputs.push(0.0);
-            local_grads.push(0.0);
-            b_prev_changes.push(0.0);
-            b.push(bias);
-
-            let mut w: Vec<f64> = vec![];
-            let mut g: Vec<f64> = vec![];
-            for _ in 0..input_features {
-                if (inputs.len() as u64) < input_features {
-                    inputs.push(0.0);
-                }
-                g.push(0.0);
-                // random both positive and negative weight
-                w.push(2f64 * rand::random::<f64>() - 1f64);
-            }
-            weights.push(w);
-            grads.push(g.clone());
-            w_prev_changes.push(g);
-        }
+        let (n_in, n_out) = (input_features as usize, output_features as usize);
+        // random both positive and negative weight
+        let weights: Vec<f64> = (0..n_in * n_out)
+            .map(|_| 2f64 * rand::random::<f64>() - 1f64)
+            .collect();
+
         Layer {
-            inputs,
-            outputs,
-            w: weights,
-            b,
-            grads,
-            w_prev_changes,
-            local_grads,
-            b_prev_changes,
+            inputs: vec![0.0; n_in],
+            outputs: vec![0.0; n_out],
+            w: Matrix::from_vec(n_out, n_in, weights),
+            b: vec![bias; n_out],
+            grads: Matrix::zeros(n_out, n_in),
+            local_grads: vec![0.0; n_out],
+            b_grads: vec![0.0; n_out],
+            batch_count: 0,
             act,
         }
     }
 
-    pub fn forward(&mut self, inputs: &Vec<f64>) -> Vec<f64> {
+    pub fn forward(&mut self, inputs: &[f64]) -> Vec<f64> {
         if inputs.len() != self.inputs.len() {
             panic!("forward: input size is wrong");
         }
 
-        let result: Vec<f64> = self
+        for ((w_j, b_j), o_j) in self
             .w
-            .iter()
+            .iter_rows()
             .zip(self.b.iter())
             .zip(self.outputs.iter_mut())
-            .map(|((w_j, b_j), o_j)| {
-                let sum = inputs
+        {
+            *o_j = inputs
+                .iter()
+                .zip(w_j.iter())
+                .fold(0.0, |s, (v, w_ji)| s + w_ji * v)
+                + b_j;
+        }
+
+        self.inputs.copy_from_slice(inputs);
+        self.act.apply(&self.outputs)
+    }
+
+    /// Forward every row of `inputs` (samples x input_features), nothing is kept for
+    /// the backward pass.
+    pub fn forward_batch(&self, inputs: &Matrix) -> Matrix {
+        if inputs.cols() != self.w.cols() {
+            panic!("forward_batch: input size is wrong");
+        }
+
+        let mut result = Matrix::zeros(inputs.rows(), self.w.rows());
+        let mut sums = vec![0.0; self.w.rows()];
+        for (r, x) in inputs.iter_rows().enumerate() {
+            for ((w_j, b_j), s_j) in self.w.iter_rows().zip(self.b.iter()).zip(sums.iter_mut()) {
+                *s_j = x
                     .iter()
                     .zip(w_j.iter())
                     .fold(0.0, |s, (v, w_ji)| s + w_ji * v)
                     + b_j;
-                *o_j = sum;
-                (self.act.func)(sum)
-            })
-            .collect();
-
-        self.inputs = inputs.clone();
+            }
+            result.row_mut(r).copy_from_slice(&self.act.apply(&sums));
+        }
         result
     }
 
-    pub fn update(&mut self, lr: f64, momentum: f64) {
-        for j in 0..self.w.len() {
-            let delta_b = lr * self.local_grads[j] + momentum * self.b_prev_changes[j];
-            self.b[j] -= delta_b; // update each neuron bias
-            self.b_prev_changes[j] = delta_b;
-            for i in 0..self.w[j].len() {
-                // update each weights
-                let delta_w = lr * self.grads[j][i] + momentum * self.w_prev_changes[j][i];
-                self.w[j][i] -= delta_w;
-                self.w_prev_changes[j][i] = delta_w;
-            }
+    /// Apply the gradients accumulated since the last step, averaged over `batch_count`,
+    /// through `optim` then reset them. `id` tells the optimizer which layer this is.
+    pub fn step(&mut self, id: usize, optim: &mut dyn Optimizer) {
+        if self.batch_count == 0 {
+            return;
         }
+        let n = self.batch_count as f64;
+        self.grads.as_mut_slice().iter_mut().for_each(|g| *g /= n);
+        self.b_grads.iter_mut().for_each(|g| *g /= n);
+
+        optim.update(2 * id, self.w.as_mut_slice(), self.grads.as_slice());
+        optim.update(2 * id + 1, &mut self.b, &self.b_grads);
+        self.zero_grad();
     }
 
     pub fn zero_grad(&mut self) {
-        for j in 0..self.outputs.len() {
-            self.local_grads[j] = 0.0;
-            for i in 0..self.grads[j].len() {
-                self.grads[j][i] = 0.0;
-            }
-        }
+        self.local_grads.iter_mut().for_each(|g| *g = 0.0);
+        self.b_grads.iter_mut().for_each(|g| *g = 0.0);
+        self.grads.fill(0.0);
+        self.batch_count = 0;
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Net {
     pub layers: Vec<Layer>,
     pub parameters: u64,
@@ -122,7 +118,7 @@ impl Net {
     pub fn from_layers(layers: Vec<Layer>) -> Net {
         let mut parameters: u64 = 0;
         for l in &layers {
-            parameters += (l.w.len() * l.w[0].len()) as u64;
+            parameters += (l.w.rows() * l.w.cols()) as u64;
             parameters += l.b.len() as u64;
         }
 
@@ -143,34 +139,40 @@ impl Net {
     }
 
     /// Set this network parameters from flattened parameters.
-    pub fn set_params(&mut self, params: &Vec<f64>) {
+    pub fn set_params(&mut self, params: &[f64]) {
         if self.parameters != params.len() as u64 {
             panic!["The neural network parameters size is not equal to individual size"];
         }
         let mut idx: usize = 0;
 
         for l in self.layers.iter_mut() {
-            l.w.iter_mut().for_each(|w_j| {
-                w_j.iter_mut().for_each(|w_ji| {
-                    *w_ji = params[idx];
-                    idx += 1;
-                })
-            });
+            let w = l.w.as_mut_slice();
+            w.copy_from_slice(&params[idx..idx + w.len()]);
+            idx += w.len();
 
-            l.b.iter_mut().for_each(|b_i| {
-                *b_i = params[idx];
-                idx += 1;
-            });
+            let n_b = l.b.len();
+            l.b.copy_from_slice(&params[idx..idx + n_b]);
+            idx += n_b;
         }
     }
 
+    /// Flatten this network parameters, the inverse of `set_params`.
+    pub fn get_params(&self) -> Vec<f64> {
+        let mut params: Vec<f64> = Vec::with_capacity(self.parameters as usize);
+        for l in self.layers.iter() {
+            params.extend_from_slice(l.w.as_slice());
+            params.extend_from_slice(&l.b);
+        }
+        params
+    }
+
     pub fn zero_grad(&mut self) {
         for l in 0..self.layers.len() {
             self.layers[l].zero_grad();
         }
     }
 
-    pub fn forward(&mut self, input: &Vec<f64>) -> Vec<f64> {
+    pub fn forward(&mut self, input: &[f64]) -> Vec<f64> {
         let mut result = self.layers[0].forward(input);
         for l in 1..self.layers.len() {
             result = self.layers[l].forward(&result);
@@ -178,9 +180,20 @@ impl Net {
         result
     }
 
-    pub fn update(&mut self, lr: f64, momentum: f64) {
-        for l in 0..self.layers.len() {
-            self.layers[l].update(lr, momentum);
+    /// Forward a whole batch of samples (one per row) through the network.
+    pub fn forward_batch(&self, inputs: &Matrix) -> Matrix {
+        let mut result = self.layers[0].forward_batch(inputs);
+        for l in 1..self.layers.len() {
+            result = self.layers[l].forward_batch(&result);
+        }
+        result
+    }
+
+    /// Update every layer with its accumulated gradients through `optim`
+    pub fn step(&mut self, optim: &mut dyn Optimizer) {
+        optim.begin_step();
+        for (l, layer) in self.layers.iter_mut().enumerate() {
+            layer.step(l, optim);
         }
     }
 }
@@ -188,6 +201,7 @@ impl Net {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::optim::Sgd;
 
     #[test]
     fn test_linear_new() {
@@ -195,29 +209,51 @@ mod tests {
         assert_eq!(linear.outputs.len(), 3);
         assert_eq!(linear.inputs.len(), 2);
 
-        assert_eq!(linear.w.len(), 3);
-        assert_eq!(linear.w[0].len(), 2);
+        assert_eq!(linear.w.rows(), 3);
+        assert_eq!(linear.w.cols(), 2);
         assert_eq!(linear.b.len(), 3);
 
-        assert_eq!(linear.grads.len(), 3);
-        assert_eq!(linear.w_prev_changes.len(), 3);
-        assert_eq!(linear.grads[0].len(), 2);
-        assert_eq!(linear.w_prev_changes[0].len(), 2);
+        assert_eq!(linear.grads.rows(), 3);
+        assert_eq!(linear.grads.cols(), 2);
         assert_eq!(linear.local_grads.len(), 3);
-        assert_eq!(linear.b_prev_changes.len(), 3);
+        assert_eq!(linear.b_grads.len(), 3);
+    }
+
+    #[test]
+    fn test_step_averages_batch() {
+        let mut linear = Layer::new(2, 1, 1.0, activator::linear());
+        linear.w.fill(1.0);
+        // two accumulated samples
+        linear
+            .grads
+            .row_mut(0)
+            .copy_from_slice(&[2.0 + 4.0, 0.0 + 2.0]);
+        linear.b_grads[0] = 1.0 + 3.0;
+        linear.batch_count = 2;
+        let mut optim = Sgd::new(1.0, 0.0);
+
+        linear.step(0, &mut optim);
+        assert_eq!(linear.w.row(0), &[-2.0, 0.0]);
+        assert_eq!(linear.b[0], -1.0);
+        assert_eq!(linear.grads.row(0), &[0.0, 0.0]);
+        assert_eq!(linear.batch_count, 0);
+
+        // nothing accumulated, nothing changes
+        linear.step(0, &mut optim);
+        assert_eq!(linear.w.row(0), &[-2.0, 0.0]);
     }
 
     #[test]
     fn test_linear_forward1() {
         let mut linear = Layer::new(2, 1, 1.0, activator::sigmoid());
 
-        for j in 0..linear.w.len() {
-            for i in 0..linear.w[j].len() {
-                linear.w[j][i] = 1.0;
+        for j in 0..linear.w.rows() {
+            for i in 0..linear.w.cols() {
+                linear.w[(j, i)] = 1.0;
             }
         }
 
-        assert_eq!(linear.forward(&vec![1.0, 1.0])[0], 0.9525741268224334);
+        assert_eq!(linear.forward(&[1.0, 1.0])[0], 0.9525741268224334);
         assert_eq!(linear.outputs[0], 3.0);
     }
 
@@ -225,12 +261,12 @@ mod tests {
     fn test_linear_forward2() {
         let mut linear = Layer::new(2, 2, 1.0, activator::sigmoid());
 
-        for j in 0..linear.w.len() {
-            for i in 0..linear.w[j].len() {
-                linear.w[j][i] = (j as f64) + 1.0;
+        for j in 0..linear.w.rows() {
+            for i in 0..linear.w.cols() {
+                linear.w[(j, i)] = (j as f64) + 1.0;
             }
         }
-        let result = linear.forward(&vec![0.0, 1.0]);
+        let result = linear.forward(&[0.0, 1.0]);
         assert_eq!(linear.outputs[0], 2.0);
         assert_eq!(linear.outputs[1], 3.0);
         assert_eq!(result[0], 0.8807970779778823);
@@ -243,10 +279,30 @@ mod tests {
         layers.push(Layer::new(2, 2, 1.0, activator::relu()));
         layers.push(Layer::new(2, 1, 1.0, activator::linear()));
         let mut net = Net::from_layers(layers);
-        net.set_params(&vec![1.0, 1.0, 1.0, 1.0, 2.0, 2.0, 1.0, 1.0, 2.0]);
+        net.set_params(&[1.0, 1.0, 1.0, 1.0, 2.0, 2.0, 1.0, 1.0, 2.0]);
 
-        assert_eq!(net.layers[0].w[0], vec![1.0, 1.0]);
-        assert_eq!(net.layers[0].w[1], vec![1.0, 1.0]);
+        assert_eq!(net.layers[0].w.row(0), &[1.0, 1.0]);
+        assert_eq!(net.layers[0].w.row(1), &[1.0, 1.0]);
         assert_eq!(net.layers[0].b, vec![2.0, 2.0]);
+        assert_eq!(
+            net.get_params(),
+            vec![1.0, 1.0, 1.0, 1.0, 2.0, 2.0, 1.0, 1.0, 2.0]
+        );
+    }
+
+    #[test]
+    fn test_forward_batch() {
+        let mut net = Net::from_layers(vec![
+            Layer::new(3, 4, 1.0, activator::sigmoid()),
+            Layer::new(4, 2, 1.0, activator::linear()),
+        ]);
+
+        let samples = vec![vec![0.1, 0.2, 0.3], vec![-1.0, 0.5, 2.0]];
+        let result = net.forward_batch(&Matrix::from_rows(&samples));
+        assert_eq!(result.rows(), 2);
+        assert_eq!(result.cols(), 2);
+        for (r, x) in samples.iter().enumerate() {
+            assert_eq!(result.row(r), net.forward(x).as_slice());
+        }
     }
 }
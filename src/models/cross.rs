@@ -1,7 +1,14 @@
 //! Contains training code for variations of cross.pat dataset models.
 use crate::activator;
 use crate::loss;
+use crate::metrics::{confusion::ConfusionMatrix, curve::Curve};
 use crate::mlp;
+use crate::optim::{
+    scheduler::{CosineWarmRestarts, Scheduler},
+    Optimizer, Sgd,
+};
+use crate::trainer::callback::{Checkpoint, Logger, LossPlot};
+use crate::trainer::{early_stopping::EarlyStopping, strategy::Backprop, Trainer};
 use crate::utills;
 
 use mlp::{Layer, Net};
@@ -9,9 +16,8 @@ use std::error::Error;
 use std::fs;
 use std::io::Write;
 use std::time::{Duration, Instant};
-use utills::data::{self, confusion_count};
+use utills::data::{self, DataSet};
 use utills::graph;
-use utills::io;
 
 pub fn cross_2_4_1(lr: f64, momentum: f64, folder: &str) -> Result<(), Box<dyn Error>> {
     fn model() -> Net {
@@ -21,7 +27,15 @@ pub fn cross_2_4_1(lr: f64, momentum: f64, folder: &str) -> Result<(), Box<dyn E
         Net::from_layers(layers)
     }
 
-    cross_fit(&model, lr, momentum, folder)?;
+    cross_fit(
+        &model,
+        &|| Box::new(Sgd::new(lr, momentum)),
+        None,
+        1,
+        folder,
+        false,
+        None,
+    )?;
     Ok(())
 }
 
@@ -33,94 +47,124 @@ pub fn cross_2_8_1(lr: f64, momentum: f64, folder: &str) -> Result<(), Box<dyn E
         Net::from_layers(layers)
     }
 
-    cross_fit(&model, lr, momentum, folder)?;
+    cross_fit(
+        &model,
+        &|| Box::new(Sgd::new(lr, momentum)),
+        None,
+        1,
+        folder,
+        false,
+        None,
+    )?;
     Ok(())
 }
 
+/// Two classes with a softmax output trained on one-hot labels
+pub fn cross_2_4_2(lr: f64, momentum: f64, folder: &str) -> Result<(), Box<dyn Error>> {
+    fn model() -> Net {
+        Net::from_layers(vec![
+            Layer::new(2, 4, 1.0, activator::sigmoid()),
+            Layer::new(4, 2, 1.0, activator::softmax()),
+        ])
+    }
+
+    cross_fit(
+        &model,
+        &|| Box::new(Sgd::new(lr, momentum)),
+        None,
+        1,
+        folder,
+        true,
+        None,
+    )?;
+    Ok(())
+}
+
+/// `cross_2_4_1` with cosine annealing restarted after 500, 1000, 2000 and 4000 epochs
+pub fn cross_2_4_1_cosine(lr: f64, momentum: f64, folder: &str) -> Result<(), Box<dyn Error>> {
+    fn model() -> Net {
+        Net::from_layers(vec![
+            Layer::new(2, 4, 1.0, activator::sigmoid()),
+            Layer::new(4, 1, 1.0, activator::sigmoid()),
+        ])
+    }
+
+    cross_fit(
+        &model,
+        &|| Box::new(Sgd::new(lr, momentum)),
+        Some(&|| Box::new(CosineWarmRestarts::new(lr, 0.0, 500, 2))),
+        1,
+        folder,
+        false,
+        None,
+    )?;
+    Ok(())
+}
+
+/// With `one_hot` the model must end with a softmax layer, it is trained with
+/// categorical cross entropy on two-class labels instead of squared error on one.
+/// With `patience` a fold stops once its validation loss has not improved for that
+/// many epochs and keeps its best weights.
 pub fn cross_fit(
     model: &dyn Fn() -> Net,
-    lr: f64,
-    momentum: f64,
+    optimizer: &dyn Fn() -> Box<dyn Optimizer>,
+    scheduler: Option<&dyn Fn() -> Box<dyn Scheduler>>,
+    batch_size: usize,
     folder: &str,
+    one_hot: bool,
+    patience: Option<usize>,
 ) -> Result<(), Box<dyn Error>> {
     let (models, img) = utills::io::check_dir(folder)?;
 
-    let dataset = data::cross_dataset()?;
-    let mut loss = loss::Loss::square_err();
-    let epochs = 7500;
+    let dataset = data::cross_dataset(one_hot)?;
+    let mut trainer = Trainer::new(
+        model,
+        if one_hot {
+            loss::Loss::cce
+        } else {
+            loss::Loss::square_err
+        },
+        Box::new(Backprop::new(optimizer, scheduler, batch_size)),
+    )
+    .epochs(7500)
+    .callback(Box::new(Logger))
+    .callback(Box::new(Checkpoint::new(&models)))
+    .callback(Box::new(LossPlot::new(format!("{}/loss.png", img))));
+    if let Some(patience) = patience {
+        // the checkpoint keeps the best epoch of a fold stopped early
+        trainer = trainer.callback(Box::new(EarlyStopping::new(patience)));
+    }
+
+    let start = Instant::now();
+    let results = trainer.fit(&dataset)?;
+    let duration: Duration = start.elapsed();
 
     let mut valid_acc: Vec<f64> = vec![];
     let mut train_acc: Vec<f64> = vec![];
-    let mut loss_g = graph::LossGraph::new();
-    let mut matrix_vec: Vec<[[i32; 2]; 2]> = vec![];
-
-    let start = Instant::now();
-    for (j, dt) in dataset.cross_valid_set(0.1).iter().enumerate() {
-        // creating a model
-        let mut net = model();
-
-        // get training set and validation set
-        let training_set = &dt.0;
-        let validation_set = &dt.1;
-
-        // training
-        let mut loss_vec: Vec<f64> = vec![];
-        let mut valid_loss_vec: Vec<f64> = vec![];
-        for i in 0..epochs {
-            let mut running_loss: f64 = 0.0;
-
-            for data in training_set.get_shuffled() {
-                let result = net.forward(&data.inputs);
-
-                running_loss += loss.criterion(&result, &data.labels);
-                loss.backward(&mut net.layers);
-
-                net.update(lr, momentum);
-            }
-            running_loss /= training_set.len() as f64;
-            loss_vec.push(running_loss);
-
-            let mut valid_loss: f64 = 0.0;
-            for data in validation_set.get_datas() {
-                let result = net.forward(&data.inputs);
-                valid_loss += loss.criterion(&result, &data.labels);
-            }
-            valid_loss /= validation_set.get_datas().len() as f64;
-            valid_loss_vec.push(valid_loss);
-
-            if i == epochs - 1 {
-                let mut matrix = [[0, 0], [0, 0]];
-                for data in validation_set.get_datas() {
-                    let result = net.forward(&data.inputs);
-                    confusion_count(&mut matrix, &result, &data.labels, 0.5);
-                }
-
-                let mut matrix2 = [[0, 0], [0, 0]];
-                for data in training_set.get_datas() {
-                    let result = net.forward(&data.inputs);
-                    confusion_count(&mut matrix2, &result, &data.labels, 0.5);
-                }
-                valid_acc.push((matrix[0][0] + matrix[1][1]) as f64 / validation_set.len() as f64);
-                train_acc.push((matrix2[0][0] + matrix2[1][1]) as f64 / training_set.len() as f64);
-                matrix_vec.push(matrix);
-            }
-
-            println!(
-                "iteration: {}, epoch: {}, loss: {:.6}, valid_loss: {:.6}",
-                j, i, running_loss, valid_loss
-            );
-        }
-
-        loss_g.add_loss(loss_vec, valid_loss_vec);
-        io::save(&net.layers, format!("{}/{}.json", models, j))?;
+    let mut matrices: Vec<ConfusionMatrix> = vec![];
+    let mut curves: Vec<Curve> = vec![];
+    // class `1 0` is the first output, or the single output above 0.5
+    let positive = if one_hot { 0 } else { 1 };
+    for r in results.iter() {
+        let confusion = |set: &DataSet| {
+            let outputs = r.net.forward_batch(&set.inputs_matrix());
+            ConfusionMatrix::from_outputs(&set.labels_matrix(), &outputs, 0.5)
+        };
+        let matrix = confusion(&r.validation_set);
+        valid_acc.push(matrix.accuracy());
+        train_acc.push(confusion(&r.training_set).accuracy());
+        matrices.push(matrix);
+        let outputs = r.net.forward_batch(&r.validation_set.inputs_matrix());
+        curves.push(Curve::from_outputs(
+            &r.validation_set.labels_matrix(),
+            &outputs,
+            positive,
+        ));
     }
-    let duration: Duration = start.elapsed();
 
     let mut file = fs::File::create(format!("{}/result.txt", models))?;
     file.write_all(format!("cv_score: {:?}\n\ntime used: {:?}", valid_acc, duration).as_bytes())?;
 
-    loss_g.draw(format!("{}/loss.png", img))?;
-
     graph::hist::draw_acc_2hist(
         [&valid_acc, &train_acc],
         "Validation/Training Accuracy",
@@ -128,7 +172,17 @@ pub fn cross_fit(
         format!("{}/acc.png", img),
     )?;
 
-    graph::draw_confustion(matrix_vec, format!("{}/confusion_matrix.png", img))?;
+    let class_names = if one_hot {
+        ["1 0", "0 1"]
+    } else {
+        ["0 1", "1 0"]
+    };
+    graph::draw_confusion(
+        &matrices,
+        &class_names,
+        format!("{}/confusion_matrix.png", img),
+    )?;
+    graph::draw_roc(&curves, format!("{}/roc.png", img))?;
 
     Ok(())
 }
@@ -1,7 +1,14 @@
 //! Contains training code for variations of flood dataset models.
 use crate::activator;
 use crate::loss;
+use crate::metrics::{regression, FoldScores};
 use crate::mlp;
+use crate::optim::{
+    scheduler::{Scheduler, StepDecay},
+    Optimizer, Sgd,
+};
+use crate::trainer::callback::{Checkpoint, Logger, LossPlot};
+use crate::trainer::{early_stopping::EarlyStopping, strategy::Backprop, Trainer};
 use crate::utills;
 
 use mlp::{Layer, Net};
@@ -12,6 +19,7 @@ use std::time::{Duration, Instant};
 use utills::data;
 use utills::graph;
 use utills::io;
+use utills::scaler::Scaler;
 
 pub fn flood_8_4_1(
     lr: f64,
@@ -26,7 +34,15 @@ pub fn flood_8_4_1(
         Net::from_layers(layers)
     }
 
-    flood_fit(&model, lr, momentum, folder, standardize)?;
+    flood_fit(
+        &model,
+        &|| Box::new(Sgd::new(lr, momentum)),
+        None,
+        1,
+        folder,
+        standardize,
+        None,
+    )?;
     Ok(())
 }
 
@@ -38,7 +54,36 @@ pub fn flood_8_8_1(lr: f64, momentum: f64, folder: &str) -> Result<(), Box<dyn E
         Net::from_layers(layers)
     }
 
-    flood_fit(&model, lr, momentum, folder, true)?;
+    flood_fit(
+        &model,
+        &|| Box::new(Sgd::new(lr, momentum)),
+        None,
+        1,
+        folder,
+        true,
+        None,
+    )?;
+    Ok(())
+}
+
+/// `flood_8_4_1` with the learning rate halved every 250 epochs
+pub fn flood_8_4_1_step_decay(lr: f64, momentum: f64, folder: &str) -> Result<(), Box<dyn Error>> {
+    fn model() -> Net {
+        Net::from_layers(vec![
+            Layer::new(8, 4, 1.0, activator::sigmoid()),
+            Layer::new(4, 1, 1.0, activator::linear()),
+        ])
+    }
+
+    flood_fit(
+        &model,
+        &|| Box::new(Sgd::new(lr, momentum)),
+        Some(&|| Box::new(StepDecay::new(lr, 250, 0.5))),
+        1,
+        folder,
+        true,
+        None,
+    )?;
     Ok(())
 }
 
@@ -46,105 +91,67 @@ fn mse_to_rmse(mse: &Vec<f64>) -> Vec<f64> {
     mse.iter().map(|v| v.sqrt()).collect()
 }
 
+/// With `patience` a fold stops once its validation loss has not improved for that
+/// many epochs and keeps its best weights.
 pub fn flood_fit(
     model: &dyn Fn() -> Net,
-    lr: f64,
-    momentum: f64,
+    optimizer: &dyn Fn() -> Box<dyn Optimizer>,
+    scheduler: Option<&dyn Fn() -> Box<dyn Scheduler>>,
+    batch_size: usize,
     folder: &str,
     standardize: bool,
+    patience: Option<usize>,
 ) -> Result<(), Box<dyn Error>> {
-    let (models, img) = ("", "img");
+    let (models, img) = io::check_dir(folder)?;
 
     let dataset = data::flood_dataset()?;
-    let mut loss = loss::Loss::square_err();
-    let epochs = 1000;
-
-    let mut cv_valid_loss: Vec<f64> = vec![];
-    let mut cv_train_loss: Vec<f64> = vec![];
+    let mut trainer = Trainer::new(
+        model,
+        loss::Loss::square_err,
+        Box::new(Backprop::new(optimizer, scheduler, batch_size)),
+    )
+    .epochs(1000)
+    .callback(Box::new(Logger))
+    .callback(Box::new(Checkpoint::new(&models)))
+    .callback(Box::new(LossPlot::new(format!("{}/loss.png", img))));
+    if let Some(patience) = patience {
+        // the checkpoint keeps the best epoch of a fold stopped early
+        trainer = trainer.callback(Box::new(EarlyStopping::new(patience)));
+    }
+    if standardize {
+        // saved with each checkpoint, a loaded model takes raw inputs
+        trainer = trainer.scale_inputs(Scaler::standard);
+    }
 
-    let mut r2_score: Vec<f64> = vec![];
-    let mut loss_g = graph::LossGraph::new();
     let start = Instant::now();
-    for (j, dt) in dataset.cross_valid_set(0.1).iter().enumerate() {
-        // creating a model
-        let mut net = model();
-
-        // get training set and validation set
-        let (training_set, validation_set) = if standardize {
-            dt.0.standardization(&dt.1)
-        } else {
-            (dt.0.clone(), dt.1.clone())
-        };
-        //let training_set = data::minmax_norm(&dt.0, dt.0.min(), dt.0.max());
-        //let validation_set = data::minmax_norm(&dt.1, dt.1.min(), dt.1.max());
-
-        // training
-        let mut loss_vec: Vec<f64> = vec![];
-        let mut valid_loss_vec: Vec<f64> = vec![];
-        for i in 0..epochs {
-            let mut running_loss: f64 = 0.0;
-
-            for data in training_set.get_shuffled() {
-                let result = net.forward(&data.inputs);
-
-                running_loss += loss.criterion(&result, &data.labels);
-                loss.backward(&mut net.layers);
-
-                net.update(lr, momentum);
-            }
-            running_loss /= training_set.len() as f64;
-            loss_vec.push(running_loss);
-
-            let mut valid_loss: f64 = 0.0;
-            for data in validation_set.get_datas() {
-                let result = net.forward(&data.inputs);
-                valid_loss += loss.criterion(&result, &data.labels);
-            }
-            valid_loss /= validation_set.get_datas().len() as f64;
-            valid_loss_vec.push(valid_loss);
-
-            if i == epochs - 1 {
-                // log score
-                let label_mean = validation_set.get_datas().iter().fold(0f64, |mean, val| {
-                    mean + val.labels[0] / validation_set.len() as f64
-                });
-
-                let mut total_sum_sqr = 0f64;
-                let mut sum_sqr = 0f64;
-
-                for data in validation_set.get_datas() {
-                    let result = net.forward(&data.inputs);
-                    sum_sqr += (data.labels[0] - result[0]).powi(2);
-                    total_sum_sqr += (data.labels[0] - label_mean).powi(2);
-                }
-
-                r2_score.push(1.0 - (sum_sqr / total_sum_sqr));
-                cv_valid_loss.push(valid_loss);
-                cv_train_loss.push(running_loss);
-            }
-
-            println!(
-                "iteration: {}, epoch: {}, loss: {:.6}, valid_loss: {:.6}",
-                j, i, running_loss, valid_loss
-            );
-        }
-
-        loss_g.add_loss(loss_vec, valid_loss_vec);
-        io::save(&net.layers, format!("{}/{}.json", models, j))?;
-    }
+    let results = trainer.fit(&dataset)?;
     let duration: Duration = start.elapsed();
 
+    let mut scores = FoldScores::new();
+    for r in results.iter() {
+        let validation_set = &r.validation_set;
+        let desired = validation_set.labels_matrix();
+        let result = r.net.forward_batch(&validation_set.inputs_matrix());
+
+        scores.push("r2", regression::r2(&desired, &result));
+        scores.push("rmse", regression::rmse(&desired, &result));
+        scores.push("mae", regression::mae(&desired, &result));
+        scores.push("valid_loss", *r.valid_loss.last().unwrap());
+        scores.push("train_loss", *r.loss.last().unwrap());
+    }
+    let cv_valid_loss = scores.get("valid_loss").unwrap().to_vec();
+    let cv_train_loss = scores.get("train_loss").unwrap().to_vec();
+    let r2_score = scores.get("r2").unwrap().to_vec();
+
     let mut file = fs::File::create(format!("{}/result.txt", models))?;
     file.write_all(
         format!(
-            "cv_score: {:?}\n\nr2_score: {:?}\n\ntime used: {:?}",
-            cv_valid_loss, r2_score, duration
+            "cv_score: {:?}\n\nr2_score: {:?}\n\ntime used: {:?}\n\n{}",
+            cv_valid_loss, r2_score, duration, scores
         )
         .as_bytes(),
     )?;
 
-    loss_g.draw(format!("{}/loss.png", img))?;
-
     graph::hist::draw_acc_2hist(
         [&mse_to_rmse(&cv_valid_loss), &mse_to_rmse(&cv_train_loss)],
         "Validation/Training RMSE",
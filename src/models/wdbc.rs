@@ -2,16 +2,38 @@ use std::{error::Error, time::Instant};
 
 use crate::{
     activator,
-    ga::{self, Individual},
+    ga::{
+        crossover,
+        island::{IslandModel, Topology},
+        mutation,
+        nsga::Nsga2,
+        GeneticAlgorithm,
+    },
     loss,
+    metrics::{
+        classification::Average,
+        confusion::ConfusionMatrix,
+        curve::{Criterion, Curve},
+    },
     mlp::{self, Layer, Net},
+    trainer::{
+        callback::{Logger, ProgressPlot},
+        strategy::{Genetic, Islands},
+        Strategy, Trainer,
+    },
     utills::{
-        data::{self, confusion_count},
-        graph,
+        data::{self, DataSet},
+        graph, parallel,
+        split::{Holdout, StratifiedKFold},
     },
 };
 
 const IMGPATH: &str = "report/assignment_3/images";
+/// Seed of the cross-validation folds, the same folds on every run
+const SEED: u64 = 2022;
+/// Cost of a missed malignant tumor (false negative) against a false alarm (false positive)
+const COST: [[f64; 2]; 2] = [[0.0, 1.0], [10.0, 0.0]];
+const THRESHOLD: f64 = 0.5;
 
 pub fn wdbc_30_15_1() {
     fn model() -> Net {
@@ -20,7 +42,7 @@ pub fn wdbc_30_15_1() {
         layers.push(Layer::new(15, 1, 1.0, activator::sigmoid()));
         Net::from_layers(layers)
     }
-    wdbc_ga(&model, "wdbc-30-15-1", IMGPATH).unwrap();
+    wdbc_ga(&model, single_ga(), "wdbc-30-15-1", IMGPATH).unwrap();
 }
 
 pub fn wdbc_30_7_1() {
@@ -30,7 +52,7 @@ pub fn wdbc_30_7_1() {
         layers.push(Layer::new(7, 1, 1.0, activator::sigmoid()));
         Net::from_layers(layers)
     }
-    wdbc_ga(&model, "wdbc-30-7-1", IMGPATH).unwrap();
+    wdbc_ga(&model, single_ga(), "wdbc-30-7-1", IMGPATH).unwrap();
 }
 
 pub fn wdbc_30_15_7_1() {
@@ -41,110 +63,211 @@ pub fn wdbc_30_15_7_1() {
         layers.push(Layer::new(7, 1, 1.0, activator::sigmoid()));
         Net::from_layers(layers)
     }
-    wdbc_ga(&model, "wdbc-30-15-7-1", IMGPATH).unwrap();
+    // a single population converges too early on the deeper net
+    wdbc_ga(&model, island_ga(), "wdbc-30-15-7-1", IMGPATH).unwrap();
 }
 
-/// train mlp with genitic algorithm
-pub fn wdbc_ga(model: &dyn Fn() -> Net, folder: &str, imgpath: &str) -> Result<(), Box<dyn Error>> {
-    let dataset = data::wdbc_dataset()?;
-    let mut valid_acc: Vec<f64> = vec![];
-    let mut train_acc: Vec<f64> = vec![];
-    let mut train_proc: Vec<Vec<(i32, f64)>> = Vec::with_capacity(10);
-    for _ in 0..10 {
-        train_proc.push(vec![]);
+pub fn wdbc_30_15_1_nsga() {
+    fn model() -> Net {
+        Net::from_layers(vec![
+            Layer::new(30, 15, 1.0, activator::sigmoid()),
+            Layer::new(15, 1, 1.0, activator::sigmoid()),
+        ])
     }
+    wdbc_nsga(&model, "wdbc-30-15-1-nsga", IMGPATH).unwrap();
+}
 
-    let mut matrix_vec: Vec<[[i32; 2]; 2]> = vec![];
-    let threshold = 0.5;
-    let max_gen = 200;
+/// Accuracy of `net` on `training_set` with a small bonus for a lower mean squared error
+fn fitness(net: &mut Net, training_set: &DataSet) -> f64 {
+    let labels = training_set.labels_matrix();
+    let outputs = net.forward_batch(&training_set.inputs_matrix());
+    let run_loss =
+        loss::Loss::square_err().criterion_batch(&outputs, &labels) / training_set.len() as f64;
+    ConfusionMatrix::from_outputs(&labels, &outputs, THRESHOLD).accuracy() + 0.001 / run_loss
+}
+
+/// One population of 25
+pub fn single_ga() -> Box<dyn Strategy> {
+    Box::new(Genetic::new(
+        &fitness,
+        GeneticAlgorithm::new(25, SEED)
+            .elitism(5)
+            .max_gen(200)
+            .threads(parallel::available_threads())
+            .mutation(Box::new(mutation::Uniform::new(0.02))),
+    ))
+}
+
+/// Four islands of 25 with different crossovers and mutations on a ring
+pub fn island_ga() -> Box<dyn Strategy> {
+    let island = |seed: u64| {
+        GeneticAlgorithm::new(25, seed)
+            .elitism(3)
+            .max_gen(200)
+            .threads(parallel::available_threads())
+    };
+    let islands = vec![
+        island(SEED).mutation(Box::new(mutation::Uniform::new(0.02))),
+        island(SEED + 1)
+            .crossover(Box::new(crossover::Blend::new(0.5)))
+            .mutation(Box::new(mutation::Gaussian::new(0.05, 0.3))),
+        island(SEED + 2)
+            .crossover(Box::new(crossover::Sbx::new(2.0)))
+            .mutation(Box::new(mutation::Cauchy::new(0.02, 0.1))),
+        island(SEED + 3)
+            .crossover(Box::new(crossover::TwoPoint))
+            .mutation(Box::new(mutation::Uniform::new(0.05))),
+    ];
+    Box::new(Islands::new(
+        &fitness,
+        IslandModel::new(islands, SEED)
+            .topology(Topology::Ring)
+            .interval(10)
+            .migrants(2),
+    ))
+}
+
+/// train mlp with genitic algorithm `strategy`
+pub fn wdbc_ga(
+    model: &dyn Fn() -> Net,
+    strategy: Box<dyn Strategy>,
+    folder: &str,
+    imgpath: &str,
+) -> Result<(), Box<dyn Error>> {
+    let dataset = data::wdbc_dataset()?;
+
+    let mut trainer = Trainer::new(model, loss::Loss::square_err, strategy)
+        .epochs(200)
+        .splitter(Box::new(StratifiedKFold::new(10, SEED)))
+        .preprocess(DataSet::minmax_norm)
+        .callback(Box::new(Logger))
+        .callback(Box::new(ProgressPlot::new(
+            format!("{}/{}/train_proc.png", imgpath, folder),
+            1.1,
+        )));
 
     let start = Instant::now();
-    for (j, dt) in dataset.cross_valid_set(0.1).iter().enumerate() {
-        let mut net = model();
-        let (training_set, validation_set) = dt.0.minmax_norm(&dt.1);
-        let mut loss = loss::Loss::square_err();
-
-        // training with GA
-        let mut pop = ga::init_pop(&net, 25);
-        let mut best_ind = pop[0].clone();
-
-        for k in 0..max_gen {
-            let mut max_fitness = f64::MIN;
-            let mut local_best_ind = pop[0].clone();
-
-            for p in pop.iter_mut() {
-                ga::assign_ind(&mut net, &p);
-                let mut matrix = [[0, 0], [0, 0]];
-                let mut run_loss = 0.0;
-                for data in training_set.get_shuffled() {
-                    let result = net.forward(&data.inputs);
-                    run_loss += loss.criterion(&result, &data.labels);
-                    confusion_count(&mut matrix, &result, &data.labels, threshold);
-                }
-                let fitness = ((matrix[0][0] + matrix[1][1]) as f64 / training_set.len() as f64)
-                    + 0.001 / (run_loss / training_set.len() as f64);
-                p.set_fitness(fitness);
-                train_proc[j].push((k, fitness)); // track training progress
-
-                if fitness > max_fitness {
-                    max_fitness = fitness;
-                    local_best_ind = p.clone();
-                }
-                // store best individual for all generation
-                if best_ind.fitness < fitness {
-                    best_ind = p.clone();
-                }
-            }
-
-            // selection
-            let p1 = ga::selection::d_tornament(&pop);
-            let mating_result = ga::mating(&p1);
-            let mut mut_result = ga::mutate(&mating_result, 20, 0.02);
-
-            let mut new_pop: Vec<Individual> = vec![];
-            new_pop.append(&mut mut_result);
-            let pop_need = pop.len() - new_pop.len();
-
-            // elitsm
-            for _ in 0..pop_need {
-                new_pop.push(local_best_ind.clone());
-            }
-
-            pop = new_pop;
-            println!("[{}, {}] max_fitness: {:.3}", j, k, max_fitness);
-        }
-
-        ga::assign_ind(&mut net, &best_ind);
-        let mut matrix = [[0, 0], [0, 0]];
-        for data in validation_set.get_datas() {
-            let result = net.forward(&data.inputs);
-            confusion_count(&mut matrix, &result, &data.labels, threshold);
-        }
-        valid_acc.push((matrix[0][0] + matrix[1][1]) as f64 / validation_set.len() as f64);
-        matrix_vec.push(matrix);
-        let mut matrix_t = [[0, 0], [0, 0]];
-        for data in training_set.get_datas() {
-            let result = net.forward(&data.inputs);
-            confusion_count(&mut matrix_t, &result, &data.labels, threshold);
-        }
-        train_acc.push((matrix_t[0][0] + matrix_t[1][1]) as f64 / training_set.len() as f64);
-        //io::save(&net.layers, format!("models/{}/{}.json", folder, j))?;
-    }
+    let results = trainer.fit(&dataset)?;
     let duration = start.elapsed();
     println!("Time used: {:.3} sec", duration.as_secs_f32());
 
+    let mut valid_acc: Vec<f64> = vec![];
+    let mut train_acc: Vec<f64> = vec![];
+    let mut matrices: Vec<ConfusionMatrix> = vec![];
+    let mut curves: Vec<Curve> = vec![];
+    let mut auc: Vec<f64> = vec![];
+    for r in results.iter() {
+        let labels = r.training_set.labels_matrix();
+        let outputs = r.net.forward_batch(&r.training_set.inputs_matrix());
+        train_acc.push(ConfusionMatrix::from_outputs(&labels, &outputs, THRESHOLD).accuracy());
+        // decision threshold chosen on the training set, then used on the validation set
+        let (tuned, _) = Curve::from_outputs(&labels, &outputs, 1).best(Criterion::Cost(COST));
+
+        let labels = r.validation_set.labels_matrix();
+        let outputs = r.net.forward_batch(&r.validation_set.inputs_matrix());
+        let matrix = ConfusionMatrix::from_outputs(&labels, &outputs, tuned);
+        println!(
+            "threshold {:.4}: accuracy {:.4}, malignant recall {:.4}",
+            tuned,
+            matrix.accuracy(),
+            matrix.recall(Average::Binary(1))
+        );
+        valid_acc.push(matrix.accuracy());
+        matrices.push(matrix);
+        let curve = Curve::from_outputs(&labels, &outputs, 1);
+        auc.push(curve.roc_auc());
+        curves.push(curve);
+    }
+    let mut total = ConfusionMatrix::new(2);
+    matrices.iter().for_each(|m| total.merge(m));
+    println!(
+        "All folds: accuracy {:.4}, malignant recall {:.4}, mcc {:.4}",
+        total.accuracy(),
+        total.recall(Average::Binary(1)),
+        total.mcc()
+    );
+    println!("ROC AUC: {:?}", auc);
+
     graph::hist::draw_acc_2hist(
         [&valid_acc, &train_acc],
         "Training & Validation Accuray",
         ("Iterations", "Accuracy"),
         format!("{}/{}/accuracy.png", imgpath, folder),
     )?;
-    graph::draw_confustion(matrix_vec, format!("{}/{}/conf_mat.png", imgpath, folder))?;
-    graph::draw_ga_progress(
-        &train_proc,
-        format!("{}/{}/train_proc.png", imgpath, folder),
-        1.1,
+    graph::draw_confusion(
+        &matrices,
+        &["B", "M"],
+        format!("{}/{}/conf_mat.png", imgpath, folder),
     )?;
+    graph::draw_roc(&curves, format!("{}/{}/roc.png", imgpath, folder))?;
+    graph::draw_pr(&curves, format!("{}/{}/pr.png", imgpath, folder))?;
+
+    Ok(())
+}
 
+/// Evolve training accuracy, mean squared error and weight sparsity together with
+/// NSGA-II on a holdout split, then report the Pareto front on the validation set and
+/// draw its accuracy/error trade-off
+pub fn wdbc_nsga(
+    model: &dyn Fn() -> Net,
+    folder: &str,
+    imgpath: &str,
+) -> Result<(), Box<dyn Error>> {
+    let dataset = data::wdbc_dataset()?;
+    let (training_set, validation_set, _) = Holdout::new(0.2, 0.0, SEED).split3(&dataset);
+    let (training_set, validation_set) = training_set.minmax_norm(&validation_set);
+    let (inputs, labels) = (training_set.inputs_matrix(), training_set.labels_matrix());
+
+    let objectives = |net: &mut Net| -> Vec<f64> {
+        let outputs = net.forward_batch(&inputs);
+        let mse =
+            loss::Loss::square_err().criterion_batch(&outputs, &labels) / training_set.len() as f64;
+        let weights: Vec<f64> = net
+            .layers
+            .iter()
+            .flat_map(|l| l.w.as_slice().to_vec())
+            .collect();
+        // share of weights close enough to 0 to be pruned
+        let sparsity =
+            weights.iter().filter(|w| w.abs() < 0.05).count() as f64 / weights.len() as f64;
+        vec![
+            ConfusionMatrix::from_outputs(&labels, &outputs, THRESHOLD).accuracy(),
+            -mse,
+            sparsity,
+        ]
+    };
+    let mut nsga = Nsga2::new(50, SEED)
+        .max_gen(200)
+        .threads(parallel::available_threads())
+        .mutation(Box::new(mutation::Uniform::new(0.02)));
+
+    let start = Instant::now();
+    let mut net = model();
+    let front = nsga.run(&net, &objectives);
+    println!("Time used: {:.3} sec", start.elapsed().as_secs_f32());
+
+    let (valid_inputs, valid_labels) = (
+        validation_set.inputs_matrix(),
+        validation_set.labels_matrix(),
+    );
+    for (ind, o) in front.iter() {
+        net.set_params(&ind.chromosome);
+        let outputs = net.forward_batch(&valid_inputs);
+        println!(
+            "accuracy {:.4}, mse {:.4}, sparsity {:.4}: validation accuracy {:.4}",
+            o[0],
+            -o[1],
+            o[2],
+            ConfusionMatrix::from_outputs(&valid_labels, &outputs, THRESHOLD).accuracy()
+        );
+    }
+
+    let points: Vec<Vec<f64>> = front.iter().map(|(_, o)| vec![o[0], -o[1]]).collect();
+    graph::draw_pareto_front(
+        &points,
+        (0, 1),
+        ("Training Accuracy", "Training MSE"),
+        format!("{}/{}/pareto.png", imgpath, folder),
+    )?;
     Ok(())
 }
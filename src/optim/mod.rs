@@ -0,0 +1,333 @@
+//! Optimizers that turn the averaged gradients of a `Net` into parameter updates.
+//!
+//! Every optimizer keeps its own state per parameter group. `Net::step` gives each
+//! layer weights and biases a stable group id, so the same optimizer must be used for
+//! a single network only.
+pub mod scheduler;
+
+pub trait Optimizer {
+    /// Called once at the start of every `Net::step`
+    fn begin_step(&mut self) {}
+
+    /// Update parameter group `id` in place from its averaged gradients
+    fn update(&mut self, id: usize, params: &mut [f64], grads: &[f64]);
+
+    fn lr(&self) -> f64;
+
+    fn set_lr(&mut self, lr: f64);
+}
+
+/// Get state of parameter group `id`, initialized with zeros on first use.
+fn state(states: &mut Vec<Vec<f64>>, id: usize, len: usize) -> &mut Vec<f64> {
+    if states.len() <= id {
+        states.resize(id + 1, vec![]);
+    }
+    if states[id].len() != len {
+        states[id] = vec![0.0; len];
+    }
+    &mut states[id]
+}
+
+/// Stochastic gradient descent with classical momentum
+pub struct Sgd {
+    pub lr: f64,
+    pub momentum: f64,
+    prev_changes: Vec<Vec<f64>>,
+}
+
+impl Sgd {
+    pub fn new(lr: f64, momentum: f64) -> Sgd {
+        Sgd {
+            lr,
+            momentum,
+            prev_changes: vec![],
+        }
+    }
+}
+
+impl Optimizer for Sgd {
+    fn update(&mut self, id: usize, params: &mut [f64], grads: &[f64]) {
+        let prev_changes = state(&mut self.prev_changes, id, params.len());
+        for ((p, g), prev) in params.iter_mut().zip(grads).zip(prev_changes.iter_mut()) {
+            let delta = self.lr * g + self.momentum * *prev;
+            *p -= delta;
+            *prev = delta;
+        }
+    }
+
+    fn lr(&self) -> f64 {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: f64) {
+        self.lr = lr;
+    }
+}
+
+/// Stochastic gradient descent with Nesterov momentum
+pub struct Nesterov {
+    pub lr: f64,
+    pub momentum: f64,
+    velocity: Vec<Vec<f64>>,
+}
+
+impl Nesterov {
+    pub fn new(lr: f64, momentum: f64) -> Nesterov {
+        Nesterov {
+            lr,
+            momentum,
+            velocity: vec![],
+        }
+    }
+}
+
+impl Optimizer for Nesterov {
+    fn update(&mut self, id: usize, params: &mut [f64], grads: &[f64]) {
+        let velocity = state(&mut self.velocity, id, params.len());
+        for ((p, g), v) in params.iter_mut().zip(grads).zip(velocity.iter_mut()) {
+            *v = self.momentum * *v - self.lr * g;
+            // look ahead along the new velocity
+            *p += self.momentum * *v - self.lr * g;
+        }
+    }
+
+    fn lr(&self) -> f64 {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: f64) {
+        self.lr = lr;
+    }
+}
+
+/// Adagrad, learning rate of each parameter shrinks with its sum of squared gradients
+pub struct Adagrad {
+    pub lr: f64,
+    pub eps: f64,
+    sum_sqr: Vec<Vec<f64>>,
+}
+
+impl Adagrad {
+    pub fn new(lr: f64) -> Adagrad {
+        Adagrad {
+            lr,
+            eps: 1e-10,
+            sum_sqr: vec![],
+        }
+    }
+}
+
+impl Optimizer for Adagrad {
+    fn update(&mut self, id: usize, params: &mut [f64], grads: &[f64]) {
+        let sum_sqr = state(&mut self.sum_sqr, id, params.len());
+        for ((p, g), s) in params.iter_mut().zip(grads).zip(sum_sqr.iter_mut()) {
+            *s += g * g;
+            *p -= self.lr * g / (s.sqrt() + self.eps);
+        }
+    }
+
+    fn lr(&self) -> f64 {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: f64) {
+        self.lr = lr;
+    }
+}
+
+/// RMSProp, like Adagrad with a moving average (`rho`) of squared gradients
+pub struct RmsProp {
+    pub lr: f64,
+    pub rho: f64,
+    pub eps: f64,
+    sqr_avg: Vec<Vec<f64>>,
+}
+
+impl RmsProp {
+    pub fn new(lr: f64) -> RmsProp {
+        RmsProp {
+            lr,
+            rho: 0.9,
+            eps: 1e-8,
+            sqr_avg: vec![],
+        }
+    }
+}
+
+impl Optimizer for RmsProp {
+    fn update(&mut self, id: usize, params: &mut [f64], grads: &[f64]) {
+        let sqr_avg = state(&mut self.sqr_avg, id, params.len());
+        for ((p, g), s) in params.iter_mut().zip(grads).zip(sqr_avg.iter_mut()) {
+            *s = self.rho * *s + (1.0 - self.rho) * g * g;
+            *p -= self.lr * g / (s.sqrt() + self.eps);
+        }
+    }
+
+    fn lr(&self) -> f64 {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: f64) {
+        self.lr = lr;
+    }
+}
+
+/// Adam, bias corrected moving averages of gradients and squared gradients
+pub struct Adam {
+    pub lr: f64,
+    pub beta1: f64,
+    pub beta2: f64,
+    pub eps: f64,
+    t: i32,
+    m: Vec<Vec<f64>>,
+    v: Vec<Vec<f64>>,
+}
+
+impl Adam {
+    pub fn new(lr: f64) -> Adam {
+        Adam {
+            lr,
+            beta1: 0.9,
+            beta2: 0.999,
+            eps: 1e-8,
+            t: 0,
+            m: vec![],
+            v: vec![],
+        }
+    }
+}
+
+impl Optimizer for Adam {
+    fn begin_step(&mut self) {
+        self.t += 1;
+    }
+
+    fn update(&mut self, id: usize, params: &mut [f64], grads: &[f64]) {
+        let correction1 = 1.0 - self.beta1.powi(self.t.max(1));
+        let correction2 = 1.0 - self.beta2.powi(self.t.max(1));
+        let m = state(&mut self.m, id, params.len());
+        let v = state(&mut self.v, id, params.len());
+        for (((p, g), m_i), v_i) in params
+            .iter_mut()
+            .zip(grads)
+            .zip(m.iter_mut())
+            .zip(v.iter_mut())
+        {
+            *m_i = self.beta1 * *m_i + (1.0 - self.beta1) * g;
+            *v_i = self.beta2 * *v_i + (1.0 - self.beta2) * g * g;
+            let m_hat = *m_i / correction1;
+            let v_hat = *v_i / correction2;
+            *p -= self.lr * m_hat / (v_hat.sqrt() + self.eps);
+        }
+    }
+
+    fn lr(&self) -> f64 {
+        self.lr
+    }
+
+    fn set_lr(&mut self, lr: f64) {
+        self.lr = lr;
+    }
+}
+
+/// Adam with decoupled weight decay
+pub struct AdamW {
+    pub adam: Adam,
+    pub weight_decay: f64,
+}
+
+impl AdamW {
+    pub fn new(lr: f64, weight_decay: f64) -> AdamW {
+        AdamW {
+            adam: Adam::new(lr),
+            weight_decay,
+        }
+    }
+}
+
+impl Optimizer for AdamW {
+    fn begin_step(&mut self) {
+        self.adam.begin_step();
+    }
+
+    fn update(&mut self, id: usize, params: &mut [f64], grads: &[f64]) {
+        let decay = self.adam.lr * self.weight_decay;
+        params.iter_mut().for_each(|p| *p -= decay * *p);
+        self.adam.update(id, params, grads);
+    }
+
+    fn lr(&self) -> f64 {
+        self.adam.lr
+    }
+
+    fn set_lr(&mut self, lr: f64) {
+        self.adam.lr = lr;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimize (x - 3)^2 for both parameters, return the final parameters
+    fn minimize(optim: &mut dyn Optimizer, steps: usize) -> Vec<f64> {
+        let mut params = vec![0.0, -1.0];
+        for _ in 0..steps {
+            let grads: Vec<f64> = params.iter().map(|x| 2.0 * (x - 3.0)).collect();
+            optim.begin_step();
+            optim.update(0, &mut params, &grads);
+        }
+        params
+    }
+
+    fn assert_converged(params: &[f64], tol: f64) {
+        for p in params {
+            assert!((p - 3.0).abs() < tol, "{} did not converge to 3.0", p);
+        }
+    }
+
+    #[test]
+    fn test_sgd_momentum() {
+        let mut optim = Sgd::new(0.1, 0.5);
+        let mut params = vec![1.0];
+        optim.update(0, &mut params, &[1.0]);
+        assert_eq!(params[0], 0.9);
+        optim.update(0, &mut params, &[1.0]);
+        // 0.1 * 1.0 + 0.5 * 0.1
+        assert!((params[0] - 0.75).abs() < 1e-12);
+
+        assert_converged(&minimize(&mut Sgd::new(0.1, 0.5), 200), 1e-6);
+    }
+
+    #[test]
+    fn test_adaptive_optimizers_converge() {
+        assert_converged(&minimize(&mut Nesterov::new(0.1, 0.9), 500), 1e-4);
+        assert_converged(&minimize(&mut Adagrad::new(1.0), 500), 1e-4);
+        assert_converged(&minimize(&mut RmsProp::new(0.01), 1000), 1e-2);
+        assert_converged(&minimize(&mut Adam::new(0.1), 1000), 1e-4);
+    }
+
+    #[test]
+    fn test_adamw_decay() {
+        // without gradients only the decay moves the parameters
+        let mut optim = AdamW::new(0.1, 0.5);
+        let mut params = vec![2.0];
+        optim.begin_step();
+        optim.update(0, &mut params, &[0.0]);
+        assert!((params[0] - 1.9).abs() < 1e-12);
+        assert_eq!(optim.lr(), 0.1);
+    }
+
+    #[test]
+    fn test_state_per_group() {
+        let mut optim = Sgd::new(0.1, 1.0);
+        let mut a = vec![0.0];
+        let mut b = vec![0.0, 0.0];
+        optim.update(0, &mut a, &[1.0]);
+        optim.update(1, &mut b, &[0.0, 0.0]);
+        // group 1 has no momentum from group 0
+        assert_eq!(b, vec![0.0, 0.0]);
+        optim.update(0, &mut a, &[0.0]);
+        assert!((a[0] + 0.2).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,247 @@
+//! Learning-rate schedulers, queried once per epoch by the training loop.
+use std::f64::consts::PI;
+
+pub trait Scheduler {
+    /// Learning rate to use for `epoch` (starting at 0)
+    fn lr(&mut self, epoch: usize) -> f64;
+
+    /// Receive the validation loss of the last epoch, only metric driven schedulers use it
+    fn observe(&mut self, _valid_loss: f64) {}
+}
+
+/// Keep the learning rate constant
+pub struct ConstantLr {
+    pub lr: f64,
+}
+
+impl Scheduler for ConstantLr {
+    fn lr(&mut self, _epoch: usize) -> f64 {
+        self.lr
+    }
+}
+
+/// Multiply the learning rate by `gamma` every `step_size` epochs
+pub struct StepDecay {
+    pub base_lr: f64,
+    /// At least 1, only set by `new`
+    step_size: usize,
+    pub gamma: f64,
+}
+
+impl StepDecay {
+    pub fn new(base_lr: f64, step_size: usize, gamma: f64) -> StepDecay {
+        if step_size == 0 {
+            panic!("step_size must be at least 1");
+        }
+        StepDecay {
+            base_lr,
+            step_size,
+            gamma,
+        }
+    }
+
+    pub fn step_size(&self) -> usize {
+        self.step_size
+    }
+}
+
+impl Scheduler for StepDecay {
+    fn lr(&mut self, epoch: usize) -> f64 {
+        self.base_lr * self.gamma.powi((epoch / self.step_size) as i32)
+    }
+}
+
+/// Multiply the learning rate by `gamma` every epoch
+pub struct ExponentialDecay {
+    pub base_lr: f64,
+    pub gamma: f64,
+}
+
+impl Scheduler for ExponentialDecay {
+    fn lr(&mut self, epoch: usize) -> f64 {
+        self.base_lr * self.gamma.powi(epoch as i32)
+    }
+}
+
+/// Cosine annealing from `base_lr` to `min_lr`, restarting after `t_0` epochs.
+/// Every following cycle is `t_mult` times longer than the previous one.
+pub struct CosineWarmRestarts {
+    pub base_lr: f64,
+    pub min_lr: f64,
+    /// At least 1, only set by `new`
+    t_0: usize,
+    pub t_mult: usize,
+}
+
+impl CosineWarmRestarts {
+    pub fn new(base_lr: f64, min_lr: f64, t_0: usize, t_mult: usize) -> CosineWarmRestarts {
+        if t_0 == 0 {
+            panic!("t_0 must be at least 1");
+        }
+        CosineWarmRestarts {
+            base_lr,
+            min_lr,
+            t_0,
+            t_mult,
+        }
+    }
+
+    /// Length of the first cycle
+    pub fn t_0(&self) -> usize {
+        self.t_0
+    }
+}
+
+impl Scheduler for CosineWarmRestarts {
+    fn lr(&mut self, epoch: usize) -> f64 {
+        let mut t_cur = epoch;
+        let mut t_i = self.t_0;
+        while t_cur >= t_i {
+            t_cur -= t_i;
+            t_i *= self.t_mult.max(1);
+        }
+        self.min_lr
+            + 0.5 * (self.base_lr - self.min_lr) * (1.0 + (PI * t_cur as f64 / t_i as f64).cos())
+    }
+}
+
+/// Increase the learning rate linearly from `start_factor` times the learning rate of
+/// `after` during `warmup_epochs`, then hand over to `after`.
+pub struct LinearWarmup {
+    pub warmup_epochs: usize,
+    pub start_factor: f64,
+    pub after: Box<dyn Scheduler>,
+}
+
+impl Scheduler for LinearWarmup {
+    fn lr(&mut self, epoch: usize) -> f64 {
+        if epoch < self.warmup_epochs {
+            let progress = epoch as f64 / self.warmup_epochs as f64;
+            self.after.lr(0) * (self.start_factor + (1.0 - self.start_factor) * progress)
+        } else {
+            self.after.lr(epoch - self.warmup_epochs)
+        }
+    }
+
+    fn observe(&mut self, valid_loss: f64) {
+        self.after.observe(valid_loss);
+    }
+}
+
+/// Multiply the learning rate by `factor` when the validation loss has not improved
+/// by more than `min_delta` for `patience` epochs.
+pub struct ReduceOnPlateau {
+    pub factor: f64,
+    pub patience: usize,
+    pub min_delta: f64,
+    pub min_lr: f64,
+    lr: f64,
+    best: f64,
+    bad_epochs: usize,
+}
+
+impl ReduceOnPlateau {
+    pub fn new(lr: f64, factor: f64, patience: usize) -> ReduceOnPlateau {
+        ReduceOnPlateau {
+            factor,
+            patience,
+            min_delta: 0.0,
+            min_lr: 0.0,
+            lr,
+            best: f64::MAX,
+            bad_epochs: 0,
+        }
+    }
+}
+
+impl Scheduler for ReduceOnPlateau {
+    fn lr(&mut self, _epoch: usize) -> f64 {
+        self.lr
+    }
+
+    fn observe(&mut self, valid_loss: f64) {
+        if valid_loss < self.best - self.min_delta {
+            self.best = valid_loss;
+            self.bad_epochs = 0;
+        } else {
+            self.bad_epochs += 1;
+        }
+        if self.bad_epochs > self.patience {
+            self.lr = (self.lr * self.factor).max(self.min_lr);
+            self.bad_epochs = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-12, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_decay() {
+        let mut step = StepDecay::new(0.1, 10, 0.5);
+        assert_close(step.lr(0), 0.1);
+        assert_close(step.lr(9), 0.1);
+        assert_close(step.lr(10), 0.05);
+        assert_close(step.lr(25), 0.025);
+
+        let mut exp = ExponentialDecay {
+            base_lr: 0.1,
+            gamma: 0.5,
+        };
+        assert_close(exp.lr(2), 0.025);
+    }
+
+    #[test]
+    fn test_cosine_warm_restarts() {
+        let mut cos = CosineWarmRestarts::new(1.0, 0.0, 4, 2);
+        assert_close(cos.lr(0), 1.0);
+        assert_close(cos.lr(2), 0.5);
+        // restart, second cycle is 8 epochs long
+        assert_close(cos.lr(4), 1.0);
+        assert_close(cos.lr(8), 0.5);
+        assert_close(cos.lr(12), 1.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_step_decay_zero_step() {
+        StepDecay::new(0.1, 0, 0.5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_cosine_warm_restarts_zero_period() {
+        CosineWarmRestarts::new(1.0, 0.0, 0, 2);
+    }
+
+    #[test]
+    fn test_linear_warmup() {
+        let mut warmup = LinearWarmup {
+            warmup_epochs: 4,
+            start_factor: 0.0,
+            after: Box::new(ConstantLr { lr: 0.4 }),
+        };
+        assert_close(warmup.lr(0), 0.0);
+        assert_close(warmup.lr(1), 0.1);
+        assert_close(warmup.lr(4), 0.4);
+        assert_close(warmup.lr(100), 0.4);
+    }
+
+    #[test]
+    fn test_reduce_on_plateau() {
+        let mut plateau = ReduceOnPlateau::new(1.0, 0.5, 2);
+        for loss in [3.0, 2.0, 2.0, 2.0] {
+            plateau.observe(loss);
+        }
+        assert_close(plateau.lr(4), 1.0);
+        plateau.observe(2.5);
+        assert_close(plateau.lr(5), 0.5);
+        plateau.observe(1.0);
+        assert_close(plateau.lr(6), 0.5);
+    }
+}
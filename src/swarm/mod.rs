@@ -1,4 +1,4 @@
-use rand::{distributions::Uniform, prelude::Distribution};
+use rand::{rngs::StdRng, Rng};
 
 use crate::mlp::Net;
 
@@ -11,10 +11,9 @@ pub struct Individual {
 }
 
 impl Individual {
-    pub fn new(position: Vec<f64>) -> Individual {
-        let mut rand = rand::thread_rng();
-        let dist = Uniform::from(-1.0..=1.0);
-        let speed: Vec<f64> = position.iter().map(|_i| dist.sample(&mut rand)).collect();
+    /// Particle at `position` with a random speed in range [-1, 1]
+    pub fn new(position: Vec<f64>, rng: &mut StdRng) -> Individual {
+        let speed: Vec<f64> = position.iter().map(|_| rng.gen_range(-1.0..=1.0)).collect();
         Individual {
             best_pos: position.clone(),
             position,
@@ -58,31 +57,27 @@ impl Individual {
     }
 }
 
-pub fn gen_rho(c: f64) -> f64 {
-    let mut rand = rand::thread_rng();
-    let dist = Uniform::from(0.0..=1.0);
-    dist.sample(&mut rand) * c
+pub fn gen_rho(c: f64, rng: &mut StdRng) -> f64 {
+    rng.gen_range(0.0..=1.0) * c
 }
 
 /// Create inital particles of MLP from layers
 ///
 /// return: particles
-pub fn init_particles(net: &Net, amount: u32) -> Vec<Individual> {
+pub fn init_particles(net: &Net, amount: u32, rng: &mut StdRng) -> Vec<Individual> {
     let mut inidividuals: Vec<Individual> = vec![];
     for _ in 0..amount {
         let mut position: Vec<f64> = Vec::with_capacity(net.parameters as usize);
         for l in net.layers.iter() {
-            for output in l.w.iter() {
-                for _ in output.iter() {
-                    // new random weight in range [-1, 1]
-                    position.push(2f64 * rand::random::<f64>() - 1f64);
-                }
+            for _ in l.w.as_slice().iter() {
+                // new random weight in range [-1, 1]
+                position.push(rng.gen_range(-1.0..=1.0));
             }
             for bias in l.b.iter() {
                 position.push(*bias);
             }
         }
-        inidividuals.push(Individual::new(position));
+        inidividuals.push(Individual::new(position, rng));
     }
     inidividuals
 }
@@ -99,11 +94,15 @@ impl IndividualGroup {
     }
 }
 
-pub fn init_particles_group(net: &Net, group: usize, group_size: u32) -> Vec<IndividualGroup> {
+pub fn init_particles_group(
+    net: &Net,
+    group: usize,
+    group_size: u32,
+    rng: &mut StdRng,
+) -> Vec<IndividualGroup> {
     (0..group)
-        .into_iter()
         .map(|_| {
-            let particles = init_particles(&net, group_size + 1);
+            let particles = init_particles(net, group_size + 1, rng);
             IndividualGroup {
                 particles: particles[1..].into(),
                 lbest_f: f64::MAX,
@@ -118,6 +117,7 @@ mod tests {
     use crate::{activator, mlp::Layer};
 
     use super::*;
+    use rand::SeedableRng;
 
     #[test]
     fn test_update_speed() {
@@ -125,7 +125,7 @@ mod tests {
             pos[0].powi(2) + 2.0 * pos[1]
         }
 
-        let mut p1 = Individual::new(vec![1.0, 1.0]);
+        let mut p1 = Individual::new(vec![1.0, 1.0], &mut StdRng::seed_from_u64(0));
         p1.f = 4.0;
         p1.speed = vec![0.5, 0.5];
 
@@ -152,11 +152,12 @@ mod tests {
         layers.push(Layer::new(2, 1, 1.0, activator::sigmoid()));
         let net = Net::from_layers(layers);
 
-        let groups = init_particles_group(&net, 3, 3);
+        let mut rng = StdRng::seed_from_u64(0);
+        let groups = init_particles_group(&net, 3, 3, &mut rng);
         assert_eq!(groups.len(), 3);
         assert_eq!(groups[2].particles.len(), 3);
 
-        let groups = init_particles_group(&net, 2, 5);
+        let groups = init_particles_group(&net, 2, 5, &mut rng);
         assert_eq!(groups.len(), 2);
         assert_eq!(groups[0].particles.len(), 5);
     }
@@ -0,0 +1,128 @@
+//! Callbacks hooked into `Trainer::fit` for logging, saving and plotting.
+use super::{EpochInfo, FoldResult};
+use crate::mlp::Net;
+use crate::utills::{graph, io};
+use std::error::Error;
+
+pub trait Callback {
+    fn on_fold_begin(&mut self, _fold: usize) {}
+
+    /// Return `false` to stop training the current fold
+    fn on_epoch_end(&mut self, _info: &EpochInfo, _net: &Net) -> bool {
+        true
+    }
+
+    /// Parameters the net of the fold should end with, e.g. the best epoch of
+    /// `EarlyStopping`. `Trainer::fit` loads them before any `on_fold_end`.
+    fn best_params(&self) -> Option<&[f64]> {
+        None
+    }
+
+    fn on_fold_end(
+        &mut self,
+        _fold: usize,
+        _result: &mut FoldResult,
+    ) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn on_train_end(&mut self, _results: &[FoldResult]) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+/// Print training and validation loss of every epoch
+pub struct Logger;
+
+impl Callback for Logger {
+    fn on_epoch_end(&mut self, info: &EpochInfo, _net: &Net) -> bool {
+        println!(
+            "iteration: {}, epoch: {}, loss: {:.6}, valid_loss: {:.6}",
+            info.fold, info.epoch, info.train_loss, info.valid_loss
+        );
+        true
+    }
+}
+
+/// Save the model of each fold with its scalers and training metadata to `{dir}/{fold}.json`
+pub struct Checkpoint {
+    dir: String,
+}
+
+impl Checkpoint {
+    pub fn new(dir: &str) -> Checkpoint {
+        Checkpoint { dir: dir.into() }
+    }
+}
+
+impl Callback for Checkpoint {
+    fn on_fold_end(&mut self, fold: usize, result: &mut FoldResult) -> Result<(), Box<dyn Error>> {
+        let mut model = io::ModelFile::new(&result.net);
+        model.normalization = result.normalization.clone();
+        model.metadata.fold = Some(fold);
+        model.metadata.epochs = result.loss.len();
+        model.metadata.loss = Some(result.loss_name.clone());
+        model.metadata.train_loss = result.loss.last().copied();
+        model.metadata.valid_loss = result.valid_loss.last().copied();
+        io::save_model(&model, format!("{}/{}.json", self.dir, fold))?;
+        Ok(())
+    }
+}
+
+/// Draw training/validation loss of every fold with `graph::LossGraph`
+pub struct LossPlot {
+    path: String,
+}
+
+impl LossPlot {
+    pub fn new(path: String) -> LossPlot {
+        LossPlot { path }
+    }
+}
+
+impl Callback for LossPlot {
+    fn on_train_end(&mut self, results: &[FoldResult]) -> Result<(), Box<dyn Error>> {
+        let mut loss_g = graph::LossGraph::new();
+        for r in results {
+            loss_g.add_loss(r.loss.clone(), r.valid_loss.clone());
+        }
+        loss_g.draw(self.path.clone())
+    }
+}
+
+/// Draw the fitness of every individual/particle at each epoch with
+/// `graph::draw_ga_progress`
+pub struct ProgressPlot {
+    path: String,
+    max_y: f64,
+    progress: Vec<Vec<(i32, f64)>>,
+}
+
+impl ProgressPlot {
+    pub fn new(path: String, max_y: f64) -> ProgressPlot {
+        ProgressPlot {
+            path,
+            max_y,
+            progress: vec![],
+        }
+    }
+}
+
+impl Callback for ProgressPlot {
+    fn on_fold_begin(&mut self, _fold: usize) {
+        self.progress.push(vec![]);
+    }
+
+    fn on_epoch_end(&mut self, info: &EpochInfo, _net: &Net) -> bool {
+        if let Some(fold) = self.progress.last_mut() {
+            for f in info.population {
+                fold.push((info.epoch as i32, *f));
+            }
+        }
+        true
+    }
+
+    fn on_train_end(&mut self, _results: &[FoldResult]) -> Result<(), Box<dyn Error>> {
+        graph::draw_ga_progress(&self.progress, self.path.clone(), self.max_y)
+    }
+}
@@ -0,0 +1,116 @@
+//! Stop training a fold once the monitored metric stops improving.
+use super::callback::Callback;
+use super::EpochInfo;
+use crate::mlp::Net;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Monitor {
+    ValidLoss,
+    TrainLoss,
+}
+
+/// Stop when `monitor` has not decreased by more than `min_delta` for `patience` epochs.
+/// The fold ends with the best-seen parameters when `restore_best` is set.
+pub struct EarlyStopping {
+    pub monitor: Monitor,
+    pub patience: usize,
+    pub min_delta: f64,
+    pub restore_best: bool,
+    best: f64,
+    best_epoch: usize,
+    best_params: Vec<f64>,
+    bad_epochs: usize,
+}
+
+impl EarlyStopping {
+    pub fn new(patience: usize) -> EarlyStopping {
+        EarlyStopping {
+            monitor: Monitor::ValidLoss,
+            patience,
+            min_delta: 0.0,
+            restore_best: true,
+            best: f64::MAX,
+            best_epoch: 0,
+            best_params: vec![],
+            bad_epochs: 0,
+        }
+    }
+
+    /// Epoch of the best monitored value in the last fold
+    pub fn best_epoch(&self) -> usize {
+        self.best_epoch
+    }
+}
+
+impl Callback for EarlyStopping {
+    fn on_fold_begin(&mut self, _fold: usize) {
+        self.best = f64::MAX;
+        self.best_epoch = 0;
+        self.best_params.clear();
+        self.bad_epochs = 0;
+    }
+
+    fn on_epoch_end(&mut self, info: &EpochInfo, net: &Net) -> bool {
+        let value = match self.monitor {
+            Monitor::ValidLoss => info.valid_loss,
+            Monitor::TrainLoss => info.train_loss,
+        };
+        if value < self.best - self.min_delta {
+            self.best = value;
+            self.best_epoch = info.epoch;
+            self.bad_epochs = 0;
+            if self.restore_best {
+                self.best_params = net.get_params();
+            }
+        } else {
+            self.bad_epochs += 1;
+        }
+        self.bad_epochs < self.patience
+    }
+
+    fn best_params(&self) -> Option<&[f64]> {
+        if self.restore_best && !self.best_params.is_empty() {
+            Some(&self.best_params)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activator;
+    use crate::mlp::Layer;
+
+    fn info(epoch: usize, valid_loss: f64) -> EpochInfo<'static> {
+        EpochInfo {
+            fold: 0,
+            epoch,
+            train_loss: 0.0,
+            valid_loss,
+            population: &[],
+        }
+    }
+
+    #[test]
+    fn test_early_stopping_restores_best() {
+        let mut net = Net::from_layers(vec![Layer::new(1, 1, 1.0, activator::linear())]);
+        let mut stopping = EarlyStopping::new(2);
+        stopping.min_delta = 0.1;
+        stopping.on_fold_begin(0);
+
+        net.set_params(&[1.0, 0.0]);
+        assert!(stopping.on_epoch_end(&info(0, 1.0), &net));
+        net.set_params(&[2.0, 0.0]);
+        // improves less than min_delta
+        assert!(stopping.on_epoch_end(&info(1, 0.95), &net));
+        net.set_params(&[3.0, 0.0]);
+        assert!(!stopping.on_epoch_end(&info(2, 1.5), &net));
+        assert_eq!(stopping.best_epoch(), 0);
+        assert_eq!(stopping.best_params(), Some(&[1.0, 0.0][..]));
+
+        stopping.restore_best = false;
+        assert_eq!(stopping.best_params(), None);
+    }
+}
@@ -0,0 +1,346 @@
+//! Cross-validation training loop shared by every dataset model.
+//!
+//! A `Trainer` builds a fresh `Net` for every fold, lets a `Strategy` (backprop, GA,
+//! PSO) train it epoch by epoch and reports progress to its callbacks.
+pub mod callback;
+pub mod early_stopping;
+pub mod strategy;
+
+use crate::loss::Loss;
+use crate::mlp::Net;
+use crate::utills::data::DataSet;
+use crate::utills::scaler::{FitScaler, Normalization};
+use crate::utills::split::Splitter;
+use callback::Callback;
+use std::error::Error;
+
+pub struct EpochInfo<'a> {
+    pub fold: usize,
+    pub epoch: usize,
+    pub train_loss: f64,
+    pub valid_loss: f64,
+    /// Fitness of every individual/particle evaluated this epoch, empty for backprop
+    pub population: &'a [f64],
+}
+
+pub struct FoldResult {
+    pub net: Net,
+    pub training_set: DataSet,
+    pub validation_set: DataSet,
+    pub loss: Vec<f64>,
+    pub valid_loss: Vec<f64>,
+    /// Name of the loss the fold was trained with
+    pub loss_name: String,
+    /// Scalers fit on the training set, both sets above are already scaled with them
+    pub normalization: Option<Normalization>,
+}
+
+/// How a network is trained
+pub trait Strategy {
+    /// Reset the training state for a new fold
+    fn init(&mut self, net: &Net);
+
+    /// Train `net` for one epoch and return its training loss
+    fn epoch(
+        &mut self,
+        net: &mut Net,
+        training_set: &DataSet,
+        loss: &mut Loss,
+        epoch: usize,
+    ) -> f64;
+
+    /// Receive the validation loss of the last epoch
+    fn observe(&mut self, _valid_loss: f64) {}
+
+    /// Fitness of every individual/particle of the last epoch
+    fn population(&self) -> &[f64] {
+        &[]
+    }
+
+    /// Whether training should stop before the last epoch, e.g. a terminated GA
+    fn done(&self) -> bool {
+        false
+    }
+}
+
+/// Transform of a (training, validation) fold fit on the training set, e.g.
+/// `DataSet::minmax_norm`
+pub type Preprocess = fn(&DataSet, &DataSet) -> (DataSet, DataSet);
+
+pub struct Trainer<'a> {
+    model: &'a dyn Fn() -> Net,
+    loss: fn() -> Loss,
+    strategy: Box<dyn Strategy + 'a>,
+    callbacks: Vec<Box<dyn Callback + 'a>>,
+    epochs: usize,
+    valid_percent: f64,
+    splitter: Option<Box<dyn Splitter + 'a>>,
+    preprocess: Vec<Preprocess>,
+    scale_inputs: Option<FitScaler>,
+    scale_labels: Option<FitScaler>,
+}
+
+impl<'a> Trainer<'a> {
+    pub fn new(
+        model: &'a dyn Fn() -> Net,
+        loss: fn() -> Loss,
+        strategy: Box<dyn Strategy + 'a>,
+    ) -> Trainer<'a> {
+        Trainer {
+            model,
+            loss,
+            strategy,
+            callbacks: vec![],
+            epochs: 100,
+            valid_percent: 0.1,
+            splitter: None,
+            preprocess: vec![],
+            scale_inputs: None,
+            scale_labels: None,
+        }
+    }
+
+    pub fn epochs(mut self, epochs: usize) -> Trainer<'a> {
+        self.epochs = epochs;
+        self
+    }
+
+    /// Size of each validation fold, see `DataSet::cross_valid_set`
+    pub fn valid_percent(mut self, percent: f64) -> Trainer<'a> {
+        self.valid_percent = percent;
+        self
+    }
+
+    /// Folds to train on instead of `valid_percent`, e.g. a seeded `StratifiedKFold`
+    pub fn splitter(mut self, splitter: Box<dyn Splitter + 'a>) -> Trainer<'a> {
+        self.splitter = Some(splitter);
+        self
+    }
+
+    /// Normalization or imputation applied to each (training, validation) fold, in the
+    /// order they are added
+    pub fn preprocess(mut self, f: Preprocess) -> Trainer<'a> {
+        self.preprocess.push(f);
+        self
+    }
+
+    /// Scale the inputs of each fold with a scaler fit on its training set, the scaler
+    /// is kept in `FoldResult::normalization`
+    pub fn scale_inputs(mut self, f: FitScaler) -> Trainer<'a> {
+        self.scale_inputs = Some(f);
+        self
+    }
+
+    /// Same as `scale_inputs` for the labels
+    pub fn scale_labels(mut self, f: FitScaler) -> Trainer<'a> {
+        self.scale_labels = Some(f);
+        self
+    }
+
+    pub fn callback(mut self, callback: Box<dyn Callback + 'a>) -> Trainer<'a> {
+        self.callbacks.push(callback);
+        self
+    }
+
+    /// Train a model on every cross-validation fold of `dataset`
+    pub fn fit(&mut self, dataset: &DataSet) -> Result<Vec<FoldResult>, Box<dyn Error>> {
+        let mut results: Vec<FoldResult> = vec![];
+
+        let folds = match &self.splitter {
+            Some(s) => dataset.split(s.as_ref()),
+            None => dataset.cross_valid_set(self.valid_percent),
+        };
+        for (j, dt) in folds.iter().enumerate() {
+            let (mut training_set, mut validation_set) = (dt.0.clone(), dt.1.clone());
+            for f in self.preprocess.iter() {
+                (training_set, validation_set) = f(&training_set, &validation_set);
+            }
+            let normalization = match (self.scale_inputs, self.scale_labels) {
+                (None, None) => None,
+                (inputs, labels) => Some(Normalization::fit(&training_set, inputs, labels)),
+            };
+            let (training_set, validation_set) = match &normalization {
+                Some(n) => (n.transform(&training_set), n.transform(&validation_set)),
+                None => (training_set, validation_set),
+            };
+            let valid_inputs = validation_set.inputs_matrix();
+            let valid_labels = validation_set.labels_matrix();
+
+            let mut net = (self.model)();
+            let mut loss = (self.loss)();
+            self.strategy.init(&net);
+            for cb in self.callbacks.iter_mut() {
+                cb.on_fold_begin(j);
+            }
+
+            let mut loss_vec: Vec<f64> = vec![];
+            let mut valid_loss_vec: Vec<f64> = vec![];
+            for i in 0..self.epochs {
+                let train_loss = self.strategy.epoch(&mut net, &training_set, &mut loss, i);
+                let valid_loss = loss
+                    .criterion_batch(&net.forward_batch(&valid_inputs), &valid_labels)
+                    / validation_set.len() as f64;
+                self.strategy.observe(valid_loss);
+                loss_vec.push(train_loss);
+                valid_loss_vec.push(valid_loss);
+
+                let info = EpochInfo {
+                    fold: j,
+                    epoch: i,
+                    train_loss,
+                    valid_loss,
+                    population: self.strategy.population(),
+                };
+                let mut keep_training = true;
+                for cb in self.callbacks.iter_mut() {
+                    keep_training &= cb.on_epoch_end(&info, &net);
+                }
+                if !keep_training || self.strategy.done() {
+                    break;
+                }
+            }
+            for cb in self.callbacks.iter() {
+                if let Some(params) = cb.best_params() {
+                    net.set_params(params);
+                }
+            }
+
+            let mut result = FoldResult {
+                net,
+                training_set,
+                validation_set,
+                loss: loss_vec,
+                valid_loss: valid_loss_vec,
+                loss_name: loss.name.clone(),
+                normalization,
+            };
+            for cb in self.callbacks.iter_mut() {
+                cb.on_fold_end(j, &mut result)?;
+            }
+            results.push(result);
+        }
+
+        for cb in self.callbacks.iter_mut() {
+            cb.on_train_end(&results)?;
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activator;
+    use crate::mlp::Layer;
+    use crate::optim::{Optimizer, Sgd};
+    use crate::utills::data::{Data, DataSet};
+    use crate::utills::io;
+    use crate::utills::scaler::Scaler;
+    use crate::utills::split::Holdout;
+    use callback::Checkpoint;
+    use early_stopping::EarlyStopping;
+    use strategy::Backprop;
+
+    fn model() -> Net {
+        Net::from_layers(vec![
+            Layer::new(1, 2, 1.0, activator::sigmoid()),
+            Layer::new(2, 1, 1.0, activator::linear()),
+        ])
+    }
+
+    fn dataset() -> DataSet {
+        DataSet::new(
+            (0..20)
+                .map(|i| Data {
+                    inputs: vec![i as f64 / 20.0],
+                    labels: vec![i as f64 / 10.0],
+                })
+                .collect(),
+        )
+    }
+
+    /// Stop every fold after `max` epochs
+    struct StopAfter {
+        max: usize,
+    }
+
+    impl Callback for StopAfter {
+        fn on_epoch_end(&mut self, info: &EpochInfo, _net: &Net) -> bool {
+            info.epoch + 1 < self.max
+        }
+    }
+
+    /// Load the parameters of every epoch in turn
+    struct Scripted {
+        params: Vec<Vec<f64>>,
+    }
+
+    impl Strategy for Scripted {
+        fn init(&mut self, _net: &Net) {}
+
+        fn epoch(&mut self, net: &mut Net, _: &DataSet, _: &mut Loss, epoch: usize) -> f64 {
+            net.set_params(&self.params[epoch]);
+            0.0
+        }
+    }
+
+    #[test]
+    fn test_fit_restores_best_before_fold_end() -> Result<(), Box<dyn Error>> {
+        let dir = std::env::temp_dir().join(format!("mlp_trainer_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let linear = || Net::from_layers(vec![Layer::new(1, 1, 1.0, activator::linear())]);
+        // labels are twice the inputs, the second epoch is the best one
+        let params = vec![
+            vec![0.0, 0.0],
+            vec![2.0, 0.0],
+            vec![4.0, 0.0],
+            vec![6.0, 0.0],
+        ];
+        let mut trainer = Trainer::new(&linear, Loss::square_err, Box::new(Scripted { params }))
+            .epochs(4)
+            .splitter(Box::new(Holdout::new(0.25, 0.0, 0)))
+            // registered first, it must still save the restored parameters
+            .callback(Box::new(Checkpoint::new(dir.to_str().unwrap())))
+            .callback(Box::new(EarlyStopping::new(10)));
+
+        let results = trainer.fit(&dataset())?;
+        let saved = io::load_model(dir.join("0.json"))?;
+        std::fs::remove_dir_all(&dir)?;
+
+        assert_eq!(results[0].loss.len(), 4);
+        assert_eq!(results[0].loss_name, "square_err");
+        assert_eq!(results[0].net.get_params(), vec![2.0, 0.0]);
+        assert_eq!(saved.to_net()?.get_params(), vec![2.0, 0.0]);
+        assert_eq!(saved.metadata.fold, Some(0));
+        assert_eq!(saved.metadata.epochs, 4);
+        assert_eq!(saved.metadata.loss.as_deref(), Some("square_err"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fit() -> Result<(), Box<dyn Error>> {
+        let optimizer = || Box::new(Sgd::new(0.1, 0.0)) as Box<dyn Optimizer>;
+        let mut trainer = Trainer::new(
+            &model,
+            Loss::square_err,
+            Box::new(Backprop::new(&optimizer, None, 4)),
+        )
+        .epochs(50)
+        .valid_percent(0.25)
+        .scale_labels(Scaler::standard)
+        .callback(Box::new(StopAfter { max: 3 }));
+
+        let results = trainer.fit(&dataset())?;
+        assert_eq!(results.len(), 4);
+        for r in results.iter() {
+            assert_eq!(r.loss.len(), 3);
+            assert_eq!(r.valid_loss.len(), 3);
+            assert_eq!(r.training_set.len(), 15);
+            assert_eq!(r.validation_set.len(), 5);
+            let n = r.normalization.as_ref().unwrap();
+            assert!(n.inputs.is_none());
+            assert_eq!(n.labels.as_ref().unwrap().features(), 1);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+//! Training strategies for `Trainer`: gradient descent, genetic algorithm and
+//! particle swarm.
+use super::Strategy;
+use crate::ga::{island::IslandModel, GeneticAlgorithm};
+use crate::loss::Loss;
+use crate::mlp::Net;
+use crate::optim::{scheduler::Scheduler, Optimizer};
+use crate::swarm::{self, gen_rho, IndividualGroup};
+use crate::utills::{data::DataSet, parallel};
+use rand::{rngs::StdRng, SeedableRng};
+
+/// Mean loss of `net` over every sample of `dataset`
+fn mean_loss(net: &Net, dataset: &DataSet, loss: &Loss) -> f64 {
+    loss.criterion_batch(
+        &net.forward_batch(&dataset.inputs_matrix()),
+        &dataset.labels_matrix(),
+    ) / dataset.len() as f64
+}
+
+/// Mini-batch backpropagation with a fresh optimizer (and scheduler) for each fold
+pub struct Backprop<'a> {
+    optimizer: &'a dyn Fn() -> Box<dyn Optimizer>,
+    scheduler: Option<&'a dyn Fn() -> Box<dyn Scheduler>>,
+    batch_size: usize,
+    optim: Option<Box<dyn Optimizer>>,
+    sched: Option<Box<dyn Scheduler>>,
+}
+
+impl<'a> Backprop<'a> {
+    pub fn new(
+        optimizer: &'a dyn Fn() -> Box<dyn Optimizer>,
+        scheduler: Option<&'a dyn Fn() -> Box<dyn Scheduler>>,
+        batch_size: usize,
+    ) -> Backprop<'a> {
+        Backprop {
+            optimizer,
+            scheduler,
+            batch_size,
+            optim: None,
+            sched: None,
+        }
+    }
+}
+
+impl<'a> Strategy for Backprop<'a> {
+    fn init(&mut self, _net: &Net) {
+        self.optim = Some((self.optimizer)());
+        self.sched = self.scheduler.map(|s| s());
+    }
+
+    fn epoch(
+        &mut self,
+        net: &mut Net,
+        training_set: &DataSet,
+        loss: &mut Loss,
+        epoch: usize,
+    ) -> f64 {
+        let optim = self
+            .optim
+            .as_mut()
+            .expect("init must be called before epoch");
+        if let Some(s) = self.sched.as_mut() {
+            optim.set_lr(s.lr(epoch));
+        }
+
+        let mut running_loss: f64 = 0.0;
+        for batch in training_set.get_batches(self.batch_size) {
+            for data in batch.iter() {
+                let result = net.forward(&data.inputs);
+
+                running_loss += loss.criterion(&result, &data.labels);
+                loss.backward(&mut net.layers);
+            }
+            net.step(optim.as_mut());
+        }
+        running_loss / training_set.len() as f64
+    }
+
+    fn observe(&mut self, valid_loss: f64) {
+        if let Some(s) = self.sched.as_mut() {
+            s.observe(valid_loss);
+        }
+    }
+}
+
+/// Genetic algorithm run for one generation per epoch, stopped early once the GA
+/// terminates. `fitness` is maximized, the net is left with the best individual seen
+/// so far.
+pub struct Genetic<'a> {
+    pub ga: GeneticAlgorithm,
+    fitness: &'a (dyn Fn(&mut Net, &DataSet) -> f64 + Sync),
+}
+
+impl<'a> Genetic<'a> {
+    pub fn new(
+        fitness: &'a (dyn Fn(&mut Net, &DataSet) -> f64 + Sync),
+        ga: GeneticAlgorithm,
+    ) -> Genetic<'a> {
+        Genetic { ga, fitness }
+    }
+}
+
+impl<'a> Strategy for Genetic<'a> {
+    fn init(&mut self, net: &Net) {
+        self.ga.init(net);
+    }
+
+    fn epoch(
+        &mut self,
+        net: &mut Net,
+        training_set: &DataSet,
+        loss: &mut Loss,
+        _epoch: usize,
+    ) -> f64 {
+        let fitness = self.fitness;
+        self.ga
+            .step(net, &|net: &mut Net| fitness(net, training_set));
+        mean_loss(net, training_set, loss)
+    }
+
+    fn population(&self) -> &[f64] {
+        self.ga.fitness()
+    }
+
+    fn done(&self) -> bool {
+        self.ga.done()
+    }
+}
+
+/// Island model genetic algorithm run for one generation per epoch, stopped early once
+/// every island terminates. `fitness` is maximized, the net is left with the best
+/// individual seen so far.
+pub struct Islands<'a> {
+    pub model: IslandModel,
+    fitness: &'a (dyn Fn(&mut Net, &DataSet) -> f64 + Sync),
+}
+
+impl<'a> Islands<'a> {
+    pub fn new(
+        fitness: &'a (dyn Fn(&mut Net, &DataSet) -> f64 + Sync),
+        model: IslandModel,
+    ) -> Islands<'a> {
+        Islands { model, fitness }
+    }
+}
+
+impl<'a> Strategy for Islands<'a> {
+    fn init(&mut self, net: &Net) {
+        self.model.init(net);
+    }
+
+    fn epoch(
+        &mut self,
+        net: &mut Net,
+        training_set: &DataSet,
+        loss: &mut Loss,
+        _epoch: usize,
+    ) -> f64 {
+        let fitness = self.fitness;
+        self.model
+            .step(net, &|net: &mut Net| fitness(net, training_set));
+        mean_loss(net, training_set, loss)
+    }
+
+    fn population(&self) -> &[f64] {
+        self.model.fitness()
+    }
+
+    fn done(&self) -> bool {
+        self.model.done()
+    }
+}
+
+/// Particle swarm optimization with `groups` local neighborhoods of `group_size`
+/// particles minimizing the mean training loss. Every particle is evaluated before
+/// any of them moves, so a run only depends on `seed`. The net is left with the best
+/// position found.
+pub struct ParticleSwarm {
+    pub groups: usize,
+    pub group_size: u32,
+    /// Threads evaluating the particles
+    pub threads: usize,
+    rng: StdRng,
+    swarm: Vec<IndividualGroup>,
+    fitness_vals: Vec<f64>,
+}
+
+impl ParticleSwarm {
+    pub fn new(groups: usize, group_size: u32, seed: u64) -> ParticleSwarm {
+        ParticleSwarm {
+            groups,
+            group_size,
+            threads: 1,
+            rng: StdRng::seed_from_u64(seed),
+            swarm: vec![],
+            fitness_vals: vec![],
+        }
+    }
+
+    pub fn threads(mut self, threads: usize) -> ParticleSwarm {
+        if threads == 0 {
+            panic!("threads must be at least 1");
+        }
+        self.threads = threads;
+        self
+    }
+}
+
+impl Strategy for ParticleSwarm {
+    fn init(&mut self, net: &Net) {
+        self.swarm = swarm::init_particles_group(net, self.groups, self.group_size, &mut self.rng);
+    }
+
+    fn epoch(
+        &mut self,
+        net: &mut Net,
+        training_set: &DataSet,
+        loss: &mut Loss,
+        _epoch: usize,
+    ) -> f64 {
+        let inputs = training_set.inputs_matrix();
+        let labels = training_set.labels_matrix();
+        let loss = &*loss;
+        let positions: Vec<&[f64]> = self
+            .swarm
+            .iter()
+            .flat_map(|g| g.particles.iter().map(|x| x.position.as_slice()))
+            .collect();
+        // Mean Absolute Error, F(x_i(t))
+        let evaluated = parallel::evaluate(net, &positions, self.threads, &|net: &mut Net| {
+            loss.criterion_batch(&net.forward_batch(&inputs), &labels) / training_set.len() as f64
+        });
+
+        self.fitness_vals.clear();
+        let mut evaluated = evaluated.into_iter();
+        for g in self.swarm.iter_mut() {
+            for x in g.particles.iter_mut() {
+                let f = evaluated.next().unwrap();
+                if f < x.f {
+                    x.f = f;
+                    x.best_pos = x.position.clone();
+                }
+                if f < g.lbest_f {
+                    g.lbest_f = f; // set gbest
+                    g.lbest_pos = x.position.clone();
+                }
+            }
+            for x in g.particles.iter_mut() {
+                x.update_speed(
+                    &g.lbest_pos,
+                    gen_rho(1.0, &mut self.rng),
+                    gen_rho(1.5, &mut self.rng),
+                );
+                x.change_pos();
+                self.fitness_vals.push(x.f);
+            }
+        }
+
+        let best_group = self
+            .swarm
+            .iter()
+            .reduce(|best, x| if best.lbest_f < x.lbest_f { best } else { x })
+            .unwrap();
+        net.set_params(&best_group.lbest_pos);
+        best_group.lbest_f
+    }
+
+    fn population(&self) -> &[f64] {
+        &self.fitness_vals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activator;
+    use crate::mlp::Layer;
+    use crate::utills::data::Data;
+
+    #[test]
+    fn test_particle_swarm_threads() {
+        let net = Net::from_layers(vec![
+            Layer::new(2, 3, 1.0, activator::sigmoid()),
+            Layer::new(3, 1, 1.0, activator::linear()),
+        ]);
+        let training_set = DataSet::new(
+            (0..12)
+                .map(|i| Data {
+                    inputs: vec![i as f64 / 12.0, (i % 3) as f64],
+                    labels: vec![(i % 2) as f64],
+                })
+                .collect(),
+        );
+        let run = |threads: usize| {
+            let mut pso = ParticleSwarm::new(3, 4, 7).threads(threads);
+            let mut net = net.clone();
+            let mut loss = Loss::square_err();
+            pso.init(&net);
+            let losses: Vec<f64> = (0..5)
+                .map(|i| pso.epoch(&mut net, &training_set, &mut loss, i))
+                .collect();
+            (losses, net.get_params(), pso.population().to_vec())
+        };
+        let (losses, params, population) = run(1);
+        assert_eq!(population.len(), 12);
+        // the best loss found so far never gets worse
+        assert!(losses.windows(2).all(|l| l[1] <= l[0]));
+        assert_eq!(run(4), (losses, params, population));
+    }
+}
@@ -1,7 +1,12 @@
+use super::impute::{Fill, Imputer};
 use super::io::read_lines;
+use super::loader::{Loader, Missing};
+use super::scaler::{Normalization, Scaler};
+use super::split::Splitter;
+use super::timeseries::{TimeSeries, Windowing};
+use crate::matrix::Matrix;
 use chrono::{DateTime, Duration, TimeZone, Utc};
 use rand::prelude::SliceRandom;
-use serde::Deserialize;
 use std::error::Error;
 
 pub fn max(vec: &Vec<f64>) -> f64 {
@@ -47,9 +52,10 @@ impl DataSet {
         DataSet { datas }
     }
 
+    /// Unseeded folds of `percent` of the set, see `split` for reproducible ones
     pub fn cross_valid_set(&self, percent: f64) -> Vec<(DataSet, DataSet)> {
-        if percent < 0.0 && percent > 1.0 {
-            panic!("argument percent must be in range [0, 1]")
+        if percent <= 0.0 || percent > 1.0 {
+            panic!("argument percent must be in range (0, 1]")
         }
         let k = (percent * (self.datas.len() as f64)).ceil() as usize; // fold size
         let n = (self.datas.len() as f64 / k as f64).ceil() as usize; // number of folds
@@ -79,6 +85,20 @@ impl DataSet {
         set
     }
 
+    /// (training, validation) sets of every fold of `splitter`
+    pub fn split(&self, splitter: &dyn Splitter) -> Vec<(DataSet, DataSet)> {
+        splitter
+            .split(self)
+            .iter()
+            .map(|f| (self.subset(&f.train), self.subset(&f.valid)))
+            .collect()
+    }
+
+    /// Samples at `indices`, in that order
+    pub fn subset(&self, indices: &[usize]) -> DataSet {
+        DataSet::new(indices.iter().map(|&i| self.datas[i].clone()).collect())
+    }
+
     pub fn data_points(&self) -> Vec<f64> {
         let mut data_points: Vec<f64> = vec![];
         for mut dt in self.datas.clone() {
@@ -92,82 +112,63 @@ impl DataSet {
         self.datas.len()
     }
 
+    /// Standardize inputs of this set and `valid_set` with the statistics of this set,
+    /// see `scaler::StandardScaler`
     pub fn standardization(&self, valid_set: &DataSet) -> (DataSet, DataSet) {
-        let size = self.datas[0].inputs.len();
-        let features: Vec<(Vec<f64>, Vec<f64>)> = (0..size)
-            .into_iter()
-            .map(|i| {
-                let feature = self.get_feature(i);
-                let v_feature = valid_set.get_feature(i);
-                let mean = mean(&feature);
-                let std = std(&feature, mean);
-                (
-                    standardization(&feature, mean, std),
-                    standardization(&v_feature, mean, std),
-                )
-            })
-            .collect();
-
-        let datas: Vec<Data> = self
-            .datas
-            .iter()
-            .enumerate()
-            .map(|(i, dt)| Data {
-                labels: dt.labels.clone(),
-                inputs: features.iter().map(|x| x.0[i]).collect(),
-            })
-            .collect();
+        let n = Normalization::fit(self, Some(Scaler::standard), None);
+        (n.transform(self), n.transform(valid_set))
+    }
 
-        let v_datas: Vec<Data> = valid_set
-            .datas
-            .iter()
-            .enumerate()
-            .map(|(i, dt)| Data {
-                labels: dt.labels.clone(),
-                inputs: features.iter().map(|x| x.1[i]).collect(),
-            })
-            .collect();
+    /// Min-max normalize inputs of this set and `valid_set` with the range of this set,
+    /// see `scaler::MinMaxScaler`
+    pub fn minmax_norm(&self, valid_set: &DataSet) -> (DataSet, DataSet) {
+        let n = Normalization::fit(self, Some(Scaler::minmax), None);
+        (n.transform(self), n.transform(valid_set))
+    }
 
-        (DataSet::new(datas), DataSet::new(v_datas))
+    /// Replace missing inputs of this set and `valid_set` with the feature means of this
+    /// set, see `impute::Imputer` for missing-indicator features
+    pub fn impute_mean(&self, valid_set: &DataSet) -> (DataSet, DataSet) {
+        let imputer = Imputer::fit(self, Fill::Mean, false);
+        (imputer.transform(self), imputer.transform(valid_set))
     }
 
-    pub fn minmax_norm(&self, valid_set: &DataSet) -> (DataSet, DataSet) {
-        let size = self.datas[0].inputs.len();
-        let features: Vec<(Vec<f64>, Vec<f64>)> = (0..size)
-            .into_iter()
-            .map(|i| {
-                let feature = self.get_feature(i);
-                let v_feature = valid_set.get_feature(i);
-                let min = min(&feature);
-                let max = max(&feature);
-                (
-                    minmax_norm(&feature, min, max),
-                    minmax_norm(&v_feature, min, max),
-                )
-            })
-            .collect();
+    pub fn impute_median(&self, valid_set: &DataSet) -> (DataSet, DataSet) {
+        let imputer = Imputer::fit(self, Fill::Median, false);
+        (imputer.transform(self), imputer.transform(valid_set))
+    }
 
-        let datas: Vec<Data> = self
-            .datas
-            .iter()
-            .enumerate()
-            .map(|(i, dt)| Data {
-                labels: dt.labels.clone(),
-                inputs: features.iter().map(|x| x.0[i]).collect(),
-            })
-            .collect();
+    pub fn impute_mode(&self, valid_set: &DataSet) -> (DataSet, DataSet) {
+        let imputer = Imputer::fit(self, Fill::Mode, false);
+        (imputer.transform(self), imputer.transform(valid_set))
+    }
 
-        let v_datas: Vec<Data> = valid_set
-            .datas
-            .iter()
-            .enumerate()
-            .map(|(i, dt)| Data {
-                labels: dt.labels.clone(),
-                inputs: features.iter().map(|x| x.1[i]).collect(),
-            })
-            .collect();
+    /// Every sample inputs as a row of a matrix, for `Net::forward_batch`
+    pub fn inputs_matrix(&self) -> Matrix {
+        let cols = if self.datas.is_empty() {
+            0
+        } else {
+            self.datas[0].inputs.len()
+        };
+        let mut data: Vec<f64> = Vec::with_capacity(self.datas.len() * cols);
+        for dt in self.datas.iter() {
+            data.extend_from_slice(&dt.inputs);
+        }
+        Matrix::from_vec(self.datas.len(), cols, data)
+    }
 
-        (DataSet::new(datas), DataSet::new(v_datas))
+    /// Every sample labels as a row of a matrix
+    pub fn labels_matrix(&self) -> Matrix {
+        let cols = if self.datas.is_empty() {
+            0
+        } else {
+            self.datas[0].labels.len()
+        };
+        let mut data: Vec<f64> = Vec::with_capacity(self.datas.len() * cols);
+        for dt in self.datas.iter() {
+            data.extend_from_slice(&dt.labels);
+        }
+        Matrix::from_vec(self.datas.len(), cols, data)
     }
 
     pub fn get_datas(&self) -> Vec<Data> {
@@ -195,31 +196,17 @@ impl DataSet {
         shuffled_datas.shuffle(&mut rand::thread_rng());
         shuffled_datas
     }
-}
 
-pub fn confusion_count(
-    matrix: &mut [[i32; 2]; 2],
-    result: &Vec<f64>,
-    label: &Vec<f64>,
-    threshold: f64,
-) {
-    if result[0] > threshold {
-        // true positive
-        if label[0] == 1.0 {
-            matrix[0][0] += 1
-        } else {
-            // false negative
-            matrix[1][0] += 1
-        }
-    } else if result[0] <= threshold {
-        // true negative
-        if label[0] == 0.0 {
-            matrix[1][1] += 1
-        }
-        // false positive
-        else {
-            matrix[0][1] += 1
+    /// Shuffle datas and split them into mini-batches of `batch_size`, the last batch
+    /// holds the remainder.
+    pub fn get_batches(&self, batch_size: usize) -> Vec<Vec<Data>> {
+        if batch_size == 0 {
+            panic!("batch_size must be greater than 0");
         }
+        self.get_shuffled()
+            .chunks(batch_size)
+            .map(|batch| batch.to_vec())
+            .collect()
     }
 }
 
@@ -242,42 +229,17 @@ pub fn xor_dataset() -> DataSet {
 }
 
 pub fn flood_dataset() -> Result<DataSet, Box<dyn Error>> {
-    #[derive(Deserialize)]
-    struct Record {
-        s1_t3: f64,
-        s1_t2: f64,
-        s1_t1: f64,
-        s1_t0: f64,
-        s2_t3: f64,
-        s2_t2: f64,
-        s2_t1: f64,
-        s2_t0: f64,
-        t7: f64,
-    }
-
-    let mut datas: Vec<Data> = vec![];
-    let mut reader = csv::Reader::from_path("data/flood_dataset.csv")?;
-    for record in reader.deserialize() {
-        let record: Record = record?;
-        let mut inputs: Vec<f64> = vec![];
-        // station 1
-        inputs.push(record.s1_t3);
-        inputs.push(record.s1_t2);
-        inputs.push(record.s1_t1);
-        inputs.push(record.s1_t0);
-        // station 2
-        inputs.push(record.s2_t3);
-        inputs.push(record.s2_t2);
-        inputs.push(record.s2_t1);
-        inputs.push(record.s2_t0);
-
-        let labels: Vec<f64> = vec![f64::from(record.t7)];
-        datas.push(Data { inputs, labels });
-    }
-    Ok(DataSet::new(datas))
+    Loader::new("data/flood_dataset.csv")
+        // water levels of station 1 and 2 at t-3..t0
+        .inputs([
+            "s1_t3", "s1_t2", "s1_t1", "s1_t0", "s2_t3", "s2_t2", "s2_t1", "s2_t0",
+        ])
+        .label("t7")
+        .load()
 }
 
-pub fn cross_dataset() -> Result<DataSet, Box<dyn Error>> {
+/// Labels are one-hot `[1, 0]`/`[0, 1]` when `one_hot`, else only the first column.
+pub fn cross_dataset(one_hot: bool) -> Result<DataSet, Box<dyn Error>> {
     let mut datas: Vec<Data> = vec![];
     let mut lines = read_lines("data/cross.pat")?;
     while let (Some(_), Some(Ok(l1)), Some(Ok(l2))) = (lines.next(), lines.next(), lines.next()) {
@@ -292,7 +254,9 @@ pub fn cross_dataset() -> Result<DataSet, Box<dyn Error>> {
             // class 1 0 -> 1
             // class 0 1 -> 0
             labels.push(v);
-            break;
+            if !one_hot {
+                break;
+            }
         }
         datas.push(Data { inputs, labels });
     }
@@ -300,146 +264,55 @@ pub fn cross_dataset() -> Result<DataSet, Box<dyn Error>> {
 }
 
 pub fn wdbc_dataset() -> Result<DataSet, Box<dyn Error>> {
-    let mut datas: Vec<Data> = vec![];
-    let mut lines = read_lines("data/wdbc.txt")?;
-    while let Some(Ok(line)) = lines.next() {
-        let mut inputs: Vec<f64> = vec![];
-        let mut labels: Vec<f64> = vec![]; // M (malignant) = 1.0, B (benign) = 0.0
-        let arr: Vec<&str> = line.split(",").collect();
-        if arr[1] == "M" {
-            labels.push(1.0);
-        } else if arr[1] == "B" {
-            labels.push(0.0);
-        }
-        for w in &arr[2..] {
-            let v: f64 = w.parse()?;
-            inputs.push(v);
-        }
-        datas.push(Data { inputs, labels });
-    }
-    Ok(DataSet::new(datas))
+    // id, diagnosis, 30 features; M (malignant) = 1.0, B (benign) = 0.0
+    Loader::new("data/wdbc.txt")
+        .header(false)
+        .inputs(2..32)
+        .label(1)
+        .classes(&["B", "M"])
+        .load()
 }
 
-/// Return `(desired = next five days, desired = next ten days)`
-pub fn airquality_dataset() -> Result<(DataSet, DataSet), Box<dyn Error>> {
+/// Hourly AirQuality series, missing (-200) values are kept as `NaN`
+pub fn airquality_series() -> Result<TimeSeries, Box<dyn Error>> {
     // nx is not used
-    #[derive(Deserialize, Debug)]
-    struct Record {
-        #[serde(rename = "Date")]
-        date: String,
-        #[serde(rename = "Time")]
-        time: String,
-        #[serde(rename = "PT08.S1(CO)")]
-        pt_s1: i32,
-        #[serde(rename = "C6H6(GT)")]
-        benzene: f64,
-        #[serde(rename = "PT08.S2(NMHC)")]
-        pt_s2: i32,
-        #[serde(rename = "PT08.S3(NOx)")]
-        pt_s3: i32,
-        #[serde(rename = "PT08.S4(NO2)")]
-        pt_s4: i32,
-        #[serde(rename = "PT08.S5(O3)")]
-        pt_s5: i32,
-        #[serde(rename = "T")]
-        temp: f64,
-        #[serde(rename = "RH")]
-        rh: f64,
-        #[serde(rename = "AH")]
-        ah: f64,
-    }
-    #[derive(Debug)]
-    struct RecData {
-        datetime: DateTime<Utc>,
-        input: Vec<f64>,
-        output: Vec<f64>,
-        pub okay: bool,
-    }
-    /// add to input if input is true else add to output
-    fn rec_add(recdata: &mut RecData, v: f64, input: bool) {
-        if v == -200.0 {
-            recdata.okay = false;
-        }
-        if input {
-            recdata.input.push(v);
-        } else {
-            recdata.output.push(v)
-        };
-    }
-
-    impl RecData {
-        pub fn new(record: &Record) -> RecData {
-            let datetime_str = format!("{} {}", record.date, record.time);
-            let datetime = Utc
-                .datetime_from_str(&datetime_str, "%-m/%-d/%Y %-H:%M:%S")
-                .unwrap();
-
-            let mut recdata = RecData {
-                datetime,
-                input: vec![],
-                output: vec![],
-                okay: true,
-            };
-            rec_add(&mut recdata, record.pt_s1 as f64, true);
-            rec_add(&mut recdata, record.pt_s2 as f64, true);
-            rec_add(&mut recdata, record.pt_s3 as f64, true);
-            rec_add(&mut recdata, record.pt_s4 as f64, true);
-            rec_add(&mut recdata, record.pt_s5 as f64, true);
-            rec_add(&mut recdata, record.temp, true);
-            rec_add(&mut recdata, record.rh, true);
-            rec_add(&mut recdata, record.ah, true);
-            rec_add(&mut recdata, record.benzene, false);
-            recdata
-        }
+    let (dataset, meta) = Loader::new("data/AirQualityUCI.csv")
+        .inputs([
+            "PT08.S1(CO)",
+            "PT08.S2(NMHC)",
+            "PT08.S3(NOx)",
+            "PT08.S4(NO2)",
+            "PT08.S5(O3)",
+            "T",
+            "RH",
+            "AH",
+        ])
+        .label("C6H6(GT)")
+        .meta(["Date", "Time"])
+        .sentinel(-200.0)
+        .missing(Missing::Nan)
+        .load_with_meta()?;
+
+    let mut times: Vec<DateTime<Utc>> = vec![];
+    for m in meta.iter() {
+        let datetime_str = format!("{} {}", m[0], m[1]);
+        times.push(Utc.datetime_from_str(&datetime_str, "%-m/%-d/%Y %-H:%M:%S")?);
     }
+    let datas = dataset.get_datas();
+    Ok(TimeSeries::new(
+        times,
+        datas.iter().map(|d| d.inputs.clone()).collect(),
+        datas.into_iter().map(|d| d.labels).collect(),
+    ))
+}
 
-    let mut reader = csv::Reader::from_path("data/AirQualityUCI.csv")?;
-    let mut rec_datas: Vec<RecData> = vec![];
-    for record in reader.deserialize() {
-        let rec_data = RecData::new(&record.unwrap());
-        if rec_data.okay {
-            rec_datas.push(rec_data)
-        };
-    }
-
-    //  Duration::days(5)
-    let mut datas_five: Vec<Data> = vec![];
-    let mut datas_ten: Vec<Data> = vec![];
-
-    for (i, x) in rec_datas.iter().enumerate() {
-        let next_five_days = x.datetime + Duration::days(5);
-        let next_ten_dats = x.datetime + Duration::days(10);
-
-        let mut labels_five: Vec<f64> = vec![];
-        let mut labels_ten: Vec<f64> = vec![];
-
-        for y in &rec_datas[i..] {
-            if y.datetime == next_five_days {
-                labels_five = y.output.clone();
-            }
-            if y.datetime == next_ten_dats {
-                labels_ten = y.output.clone();
-                break;
-            }
-        }
-
-        if labels_five.len() == 0 && labels_ten.len() == 0 {
-            break;
-        }
-        if labels_five.len() != 0 {
-            datas_five.push(Data {
-                inputs: x.input.clone(),
-                labels: labels_five,
-            });
-        }
-        if labels_ten.len() != 0 {
-            datas_ten.push(Data {
-                inputs: x.input.clone(),
-                labels: labels_ten,
-            });
-        }
-    }
-    Ok((DataSet::new(datas_five), DataSet::new(datas_ten)))
+/// Return `(desired = next five days, desired = next ten days)` in time order, inputs may
+/// be missing, see `DataSet::impute_mean`
+pub fn airquality_dataset() -> Result<(DataSet, DataSet), Box<dyn Error>> {
+    let series = airquality_series()?;
+    let five = Windowing::new(Duration::hours(1), 5 * 24);
+    let ten = Windowing::new(Duration::hours(1), 10 * 24);
+    Ok((series.windows(&five).0, series.windows(&ten).0))
 }
 
 #[cfg(test)]
@@ -451,7 +324,9 @@ mod tests {
         let (dt5, _) = airquality_dataset().unwrap();
 
         let dt = &dt5.cross_valid_set(0.1)[0];
-        let (train, _) = dt.0.minmax_norm(&dt.1);
+        let (train, valid) = dt.0.impute_mean(&dt.1);
+        let (train, _) = train.minmax_norm(&valid);
+        assert!(train.data_points().iter().all(|v| !v.is_nan()));
 
         for dt in train.get_datas().iter() {
             for v in dt.inputs.iter() {
@@ -463,6 +338,55 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_cross_dataset() -> Result<(), Box<dyn Error>> {
+        let dt = cross_dataset(true)?;
+        for data in dt.get_datas() {
+            assert_eq!(data.labels.len(), 2);
+            assert_eq!(data.labels[0] + data.labels[1], 1.0);
+        }
+        assert_eq!(cross_dataset(false)?.get_datas()[0].labels.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_cross_valid_set_range() {
+        xor_dataset().cross_valid_set(1.5);
+    }
+
+    #[test]
+    fn test_file_datasets() -> Result<(), Box<dyn Error>> {
+        let dt = wdbc_dataset()?;
+        assert_eq!(dt.len(), 569);
+        for data in dt.get_datas() {
+            assert_eq!(data.inputs.len(), 30);
+            assert!(data.labels == vec![0.0] || data.labels == vec![1.0]);
+        }
+        let dt = flood_dataset()?;
+        assert_eq!(dt.get_datas()[0].inputs.len(), 8);
+        assert_eq!(dt.get_datas()[0].labels.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_batches() {
+        let datas: Vec<Data> = (0..10)
+            .map(|i| Data {
+                labels: vec![0.0],
+                inputs: vec![i as f64],
+            })
+            .collect();
+        let batches = DataSet::new(datas).get_batches(4);
+
+        assert_eq!(batches.len(), 3);
+        assert_eq!(batches[0].len(), 4);
+        assert_eq!(batches[2].len(), 2);
+        let mut seen: Vec<f64> = batches.iter().flatten().map(|d| d.inputs[0]).collect();
+        seen.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(seen, (0..10).map(|i| i as f64).collect::<Vec<f64>>());
+    }
+
     #[test]
     fn test_minmax_norm() {
         let datas: Vec<Data> = (0..=10)
@@ -0,0 +1,137 @@
+//! Finite-difference check of the gradients computed by `Loss::backward`.
+use crate::loss::Loss;
+use crate::mlp::Net;
+
+pub struct GradCheck {
+    /// Gradients from backprop, flattened in the same order as `Net::get_params`
+    pub analytic: Vec<f64>,
+    /// Central difference gradients
+    pub numeric: Vec<f64>,
+    pub max_abs_diff: f64,
+    pub max_rel_err: f64,
+}
+
+impl GradCheck {
+    pub fn is_ok(&self, tolerance: f64) -> bool {
+        self.max_abs_diff < tolerance || self.max_rel_err < tolerance
+    }
+}
+
+/// Compare gradients of `loss` for one sample against central differences with step
+/// `eps`. The net parameters are left unchanged and its gradients are zeroed.
+pub fn check_gradients(
+    net: &mut Net,
+    loss: &mut Loss,
+    inputs: &[f64],
+    desired: &[f64],
+    eps: f64,
+) -> GradCheck {
+    net.zero_grad();
+    let result = net.forward(inputs);
+    loss.criterion(&result, desired);
+    loss.backward(&mut net.layers);
+
+    let mut analytic: Vec<f64> = Vec::with_capacity(net.parameters as usize);
+    for l in net.layers.iter() {
+        analytic.extend_from_slice(l.grads.as_slice());
+        analytic.extend_from_slice(&l.b_grads);
+    }
+    net.zero_grad();
+
+    let mut params = net.get_params();
+    let mut numeric: Vec<f64> = Vec::with_capacity(params.len());
+    for i in 0..params.len() {
+        let p = params[i];
+        params[i] = p + eps;
+        net.set_params(&params);
+        let result = net.forward(inputs);
+        let plus = loss.criterion(&result, desired);
+
+        params[i] = p - eps;
+        net.set_params(&params);
+        let result = net.forward(inputs);
+        let minus = loss.criterion(&result, desired);
+
+        params[i] = p;
+        numeric.push((plus - minus) / (2.0 * eps));
+    }
+    net.set_params(&params);
+
+    let mut max_abs_diff = 0f64;
+    let mut max_rel_err = 0f64;
+    for (a, n) in analytic.iter().zip(numeric.iter()) {
+        let diff = (a - n).abs();
+        max_abs_diff = max_abs_diff.max(diff);
+        max_rel_err = max_rel_err.max(diff / (a.abs() + n.abs()).max(1e-12));
+    }
+
+    GradCheck {
+        analytic,
+        numeric,
+        max_abs_diff,
+        max_rel_err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activator;
+    use crate::mlp::Layer;
+
+    fn assert_gradients(mut net: Net, mut loss: Loss, inputs: &[f64], desired: &[f64]) {
+        let check = check_gradients(&mut net, &mut loss, inputs, desired, 1e-6);
+        assert_eq!(check.analytic.len() as u64, net.parameters);
+        assert!(
+            check.max_abs_diff < 1e-6,
+            "analytic: {:?}\nnumeric: {:?}",
+            check.analytic,
+            check.numeric
+        );
+    }
+
+    #[test]
+    fn test_sigmoid_stack() {
+        let net = Net::from_layers(vec![
+            Layer::new(3, 4, 1.0, activator::sigmoid()),
+            Layer::new(4, 3, 1.0, activator::sigmoid()),
+            Layer::new(3, 2, 1.0, activator::sigmoid()),
+        ]);
+        assert_gradients(net, Loss::square_err(), &[0.2, -0.4, 0.9], &[1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_relu_stack() {
+        let net = Net::from_layers(vec![
+            Layer::new(3, 5, 0.5, activator::relu()),
+            Layer::new(5, 4, 0.5, activator::relu()),
+            Layer::new(4, 1, 0.5, activator::linear()),
+        ]);
+        assert_gradients(net, Loss::square_err(), &[0.3, 0.1, 0.7], &[2.0]);
+    }
+
+    #[test]
+    fn test_linear_stack() {
+        let net = Net::from_layers(vec![
+            Layer::new(2, 3, 1.0, activator::linear()),
+            Layer::new(3, 3, 1.0, activator::linear()),
+            Layer::new(3, 2, 1.0, activator::linear()),
+        ]);
+        assert_gradients(net, Loss::square_err(), &[1.5, -0.5], &[0.0, 3.0]);
+    }
+
+    #[test]
+    fn test_single_layer() {
+        let net = Net::from_layers(vec![Layer::new(3, 2, 1.0, activator::sigmoid())]);
+        assert_gradients(net, Loss::square_err(), &[0.5, 0.1, -0.3], &[1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_softmax_cce() {
+        let net = Net::from_layers(vec![
+            Layer::new(2, 4, 1.0, activator::sigmoid()),
+            Layer::new(4, 3, 1.0, activator::softmax()),
+        ]);
+        assert_gradients(net, Loss::cce(), &[0.5, -1.0], &[0.0, 0.0, 1.0]);
+    }
+}
@@ -2,6 +2,11 @@ use plotters::coord::Shift;
 use plotters::prelude::*;
 use std::error::Error;
 
+use crate::metrics::{
+    confusion::{ConfusionMatrix, Normalize},
+    curve::Curve,
+};
+
 pub mod hist;
 
 const FONT: &str = "Roboto Mono";
@@ -108,110 +113,228 @@ impl LossGraph {
     }
 }
 
-/// Draw confusion matrix
-pub fn draw_confustion(matrix_vec: Vec<[[i32; 2]; 2]>, path: String) -> Result<(), Box<dyn Error>> {
-    let root = BitMapBackend::new(&path, (2000, 1100)).into_drawing_area();
+/// Heat color of `v` in [0, 1], dark blue to bright red
+fn heat(v: f64) -> HSLColor {
+    HSLColor(240.0 / 360.0 - 240.0 / 360.0 * v, 0.7, 0.1 + 0.4 * v)
+}
+
+/// Draw the confusion matrix of every fold, rows are desired classes and columns
+/// predicted ones. Cells are colored by their share of the row (the recall of the
+/// class on the diagonal) and show the count with that share.
+pub fn draw_confusion(
+    matrices: &[ConfusionMatrix],
+    class_names: &[&str],
+    path: String,
+) -> Result<(), Box<dyn Error>> {
+    if matrices.is_empty() {
+        return Ok(());
+    }
+    let n = matrices[0].n_classes();
+    if class_names.len() != n {
+        panic!("every class must have a name");
+    }
+    let cols = matrices.len().min(5);
+    let rows = matrices.len().div_ceil(cols);
+    let root =
+        BitMapBackend::new(&path, (400 * cols as u32, 500 * rows as u32 + 100)).into_drawing_area();
     root.fill(&WHITE)?;
 
-    let (top, down) = root.split_vertically(1000);
+    let (top, down) = root.split_vertically(500 * rows as u32);
 
     let mut chart = ChartBuilder::on(&down)
         .margin(20)
         .margin_left(40)
         .margin_right(40)
         .x_label_area_size(40)
-        .build_cartesian_2d(0i32..50i32, 0i32..1i32)?;
+        .build_cartesian_2d(0f64..1f64, 0f64..1f64)?;
     chart
         .configure_mesh()
         .disable_y_axis()
         .disable_y_mesh()
         .x_labels(3)
-        .label_style((FONT, 40))
+        .label_style((FONT, 30))
         .draw()?;
-
     chart.draw_series((0..50).map(|x| {
-        Rectangle::new(
-            [(x, 0), (x + 1, 1)],
-            HSLColor(
-                240.0 / 360.0 - 240.0 / 360.0 * (x as f64 / 50.0),
-                0.7,
-                0.1 + 0.4 * x as f64 / 50.0,
-            )
-            .filled(),
-        )
+        let x = x as f64 / 50.0;
+        Rectangle::new([(x, 0.0), (x + 0.02, 1.0)], heat(x).filled())
     }))?;
-    // hardcode for 10 iteraions
-    let drawing_areas = top.split_evenly((2, 5));
-    let mut matrix_iter = matrix_vec.iter();
-    for (drawing_area, idx) in drawing_areas.iter().zip(1..) {
-        if let Some(matrix) = matrix_iter.next() {
-            let mut chart = ChartBuilder::on(&drawing_area)
-                .caption(
-                    format!("Iteration {}", idx),
-                    (FONT, 40, FontStyle::Bold).into_font(),
-                )
-                .margin(20)
-                .build_cartesian_2d(0i32..2i32, 2i32..0i32)?
-                .set_secondary_coord(0f64..2f64, 2f64..0f64);
-
-            chart
-                .configure_mesh()
-                .disable_axes()
-                .max_light_lines(4)
-                .disable_x_mesh()
-                .disable_y_mesh()
-                .label_style(("Hack", 20))
-                .draw()?;
-
-            chart.draw_series(
-                matrix
-                    .iter()
-                    .zip(0..)
-                    .map(|(l, y)| l.iter().zip(0..).map(move |(v, x)| (x, y, v)))
-                    .flatten()
-                    .map(|(x, y, v)| {
-                        Rectangle::new(
-                            [(x, y), (x + 1, y + 1)],
-                            HSLColor(
-                                240.0 / 360.0 - 240.0 / 360.0 * (*v as f64 / 50.0),
-                                0.7,
-                                0.1 + 0.4 * *v as f64 / 50.0,
-                            )
-                            .filled(),
-                        )
-                    }),
-            )?;
-
-            chart.draw_secondary_series(
-                matrix
-                    .iter()
-                    .zip(0..)
-                    .map(|(l, y)| l.iter().zip(0..).map(move |(v, x)| (x, y, v)))
-                    .flatten()
-                    .map(|(x, y, v)| {
-                        let text: String = if x == 0 && y == 0 {
-                            format!["TP:{}", v]
-                        } else if x == 1 && y == 0 {
-                            format!["FP:{}", v]
-                        } else if x == 0 && y == 1 {
-                            format!["FN:{}", v]
-                        } else {
-                            format!["TN:{}", v]
-                        };
-
-                        Text::new(
-                            text,
-                            ((2.0 * x as f64 + 0.7) / 2.0, (2.0 * y as f64 + 1.0) / 2.0),
-                            FONT.into_font().resize(30.0).color(&WHITE),
-                        )
-                    }),
-            )?;
-        }
+
+    let text_size = (60.0 / n as f64).clamp(10.0, 30.0);
+    for (drawing_area, (idx, matrix)) in top
+        .split_evenly((rows, cols))
+        .iter()
+        .zip(matrices.iter().enumerate())
+    {
+        let mut chart = ChartBuilder::on(drawing_area)
+            .caption(
+                format!("Iteration {}", idx + 1),
+                (FONT, 40, FontStyle::Bold).into_font(),
+            )
+            .margin(20)
+            .x_label_area_size(40)
+            .y_label_area_size(40)
+            .build_cartesian_2d(-0.5..n as f64 - 0.5, n as f64 - 0.5..-0.5)?;
+
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .disable_y_mesh()
+            .x_labels(n)
+            .y_labels(n)
+            .x_desc("Predicted")
+            .y_desc("Desired")
+            .x_label_formatter(&|x| class_label(class_names, *x))
+            .y_label_formatter(&|y| class_label(class_names, *y))
+            .label_style((FONT, 20))
+            .draw()?;
+
+        let shares = matrix.normalize(Normalize::Row);
+        let cells = || (0..n).flat_map(|d| (0..n).map(move |p| (d, p)));
+        chart.draw_series(cells().map(|(d, p)| {
+            let (x, y) = (p as f64, d as f64);
+            Rectangle::new(
+                [(x - 0.5, y - 0.5), (x + 0.5, y + 0.5)],
+                heat(shares[d][p]).filled(),
+            )
+        }))?;
+        chart.draw_series(cells().map(|(d, p)| {
+            Text::new(
+                format!("{} ({:.0}%)", matrix.counts[d][p], 100.0 * shares[d][p]),
+                (p as f64 - 0.4, d as f64),
+                FONT.into_font().resize(text_size).color(&WHITE),
+            )
+        }))?;
     }
     root.present()?;
     Ok(())
 }
 
+/// Name of the class whose cells are centered on `v`, nothing between two classes
+fn class_label(class_names: &[&str], v: f64) -> String {
+    let i = v.round() as usize;
+    if (v - v.round()).abs() < 1e-9 && i < class_names.len() {
+        class_names[i].to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Draw `points` of every fold in one chart, with the legend and an optional gray
+/// reference line
+fn draw_fold_curves(
+    points: Vec<Vec<(f64, f64)>>,
+    title: &str,
+    axes_desc: (&str, &str),
+    reference: Option<[(f64, f64); 2]>,
+    path: String,
+) -> Result<(), Box<dyn Error>> {
+    let root = BitMapBackend::new(&path, (1024, 1024)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, (FONT, CAPTION, FontStyle::Bold).into_font())
+        .margin(20)
+        .x_label_area_size(70)
+        .y_label_area_size(90)
+        .build_cartesian_2d(0f64..1f64, 0f64..1.02f64)?;
+
+    chart
+        .configure_mesh()
+        .x_desc(axes_desc.0)
+        .y_desc(axes_desc.1)
+        .axis_desc_style((FONT, AXIS_LABEL))
+        .label_style((FONT, AXIS_LABEL - 10))
+        .draw()?;
+
+    if let Some(line) = reference {
+        chart.draw_series(LineSeries::new(line, BLACK.mix(0.4)))?;
+    }
+    for (i, p) in points.into_iter().enumerate() {
+        let color = Palette99::pick(i).to_rgba();
+        chart
+            .draw_series(LineSeries::new(p, color.stroke_width(2)))?
+            .label(format!("Iteration {}", i + 1))
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color.filled()));
+    }
+
+    chart
+        .configure_series_labels()
+        .label_font((FONT, SERIE_LABEL).into_font())
+        .position(SeriesLabelPosition::LowerRight)
+        .border_style(BLACK)
+        .background_style(WHITE.mix(0.8))
+        .draw()?;
+
+    root.present()?;
+    Ok(())
+}
+
+/// Overlay the ROC curve of every fold over the chance diagonal
+pub fn draw_roc(curves: &[Curve], path: String) -> Result<(), Box<dyn Error>> {
+    draw_fold_curves(
+        curves.iter().map(|c| c.roc()).collect(),
+        "ROC Curve",
+        ("False Positive Rate", "True Positive Rate"),
+        Some([(0.0, 0.0), (1.0, 1.0)]),
+        path,
+    )
+}
+
+/// Overlay the precision-recall curve of every fold
+pub fn draw_pr(curves: &[Curve], path: String) -> Result<(), Box<dyn Error>> {
+    draw_fold_curves(
+        curves.iter().map(|c| c.pr()).collect(),
+        "Precision-Recall Curve",
+        ("Recall", "Precision"),
+        None,
+        path,
+    )
+}
+
+/// Scatter the objectives `axes` of every member of a Pareto front, with more than two
+/// objectives the front is not a line in that plane
+pub fn draw_pareto_front(
+    front: &[Vec<f64>],
+    axes: (usize, usize),
+    axes_desc: (&str, &str),
+    path: String,
+) -> Result<(), Box<dyn Error>> {
+    let points: Vec<(f64, f64)> = front.iter().map(|o| (o[axes.0], o[axes.1])).collect();
+    // range of the values with a margin, never empty
+    let range = |values: Vec<f64>| {
+        let min = values.iter().cloned().fold(f64::MAX, f64::min);
+        let max = values.iter().cloned().fold(f64::MIN, f64::max);
+        let margin = ((max - min) * 0.05).max(1e-6);
+        (min - margin)..(max + margin)
+    };
+
+    let root = BitMapBackend::new(&path, (1024, 1024)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Pareto Front", (FONT, CAPTION, FontStyle::Bold).into_font())
+        .margin(20)
+        .x_label_area_size(70)
+        .y_label_area_size(150)
+        .build_cartesian_2d(
+            range(points.iter().map(|p| p.0).collect()),
+            range(points.iter().map(|p| p.1).collect()),
+        )?;
+
+    chart
+        .configure_mesh()
+        .x_desc(axes_desc.0)
+        .y_desc(axes_desc.1)
+        .axis_desc_style((FONT, AXIS_LABEL))
+        .label_style((FONT, AXIS_LABEL - 10))
+        .draw()?;
+
+    chart.draw_series(points.into_iter().map(|p| Circle::new(p, 6, BLUE.filled())))?;
+
+    root.present()?;
+    Ok(())
+}
+
 /// Receive each cross-validation vector of each individual fitness value.
 pub fn draw_ga_progress(
     cv_fitness: &Vec<Vec<(i32, f64)>>,
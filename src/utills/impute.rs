@@ -0,0 +1,239 @@
+//! Fill missing (`NaN`) values, see `loader::Missing::Nan` to keep them when loading.
+use super::data::{Data, DataSet};
+use serde::{Deserialize, Serialize};
+
+/// Value a missing input is replaced with, computed from the known values of its feature
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fill {
+    Mean,
+    Median,
+    /// Most frequent value, the smallest one on ties
+    Mode,
+}
+
+fn known(values: &[f64]) -> Vec<f64> {
+    let mut v: Vec<f64> = values.iter().copied().filter(|v| !v.is_nan()).collect();
+    v.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    v
+}
+
+/// `fill` of `values` ignoring the missing ones, 0 when every value is missing
+pub fn fill_value(values: &[f64], fill: Fill) -> f64 {
+    let v = known(values);
+    if v.is_empty() {
+        return 0.0;
+    }
+    match fill {
+        Fill::Mean => v.iter().sum::<f64>() / v.len() as f64,
+        Fill::Median => {
+            let mid = v.len() / 2;
+            if v.len() % 2 == 0 {
+                (v[mid - 1] + v[mid]) / 2.0
+            } else {
+                v[mid]
+            }
+        }
+        Fill::Mode => {
+            // v is sorted, equal values are next to each other
+            let (mut best, mut best_count) = (v[0], 0);
+            let mut i = 0;
+            while i < v.len() {
+                let j = i + v[i..].iter().take_while(|x| **x == v[i]).count();
+                if j - i > best_count {
+                    best = v[i];
+                    best_count = j - i;
+                }
+                i = j;
+            }
+            best
+        }
+    }
+}
+
+/// Fill values of every input feature fit on a training set
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Imputer {
+    pub fill: Vec<f64>,
+    /// Features that get an extra 0/1 input telling if the value was missing
+    pub indicators: Vec<usize>,
+}
+
+impl Imputer {
+    /// With `indicators`, every feature missing somewhere in `training_set` gets a
+    /// missing-indicator input appended after the original inputs
+    pub fn fit(training_set: &DataSet, fill: Fill, indicators: bool) -> Imputer {
+        let features = match training_set.get_datas().first() {
+            Some(dt) => dt.inputs.len(),
+            None => 0,
+        };
+        let columns: Vec<Vec<f64>> = (0..features).map(|i| training_set.get_feature(i)).collect();
+        Imputer {
+            fill: columns.iter().map(|c| fill_value(c, fill)).collect(),
+            indicators: if indicators {
+                (0..features)
+                    .filter(|&i| columns[i].iter().any(|v| v.is_nan()))
+                    .collect()
+            } else {
+                vec![]
+            },
+        }
+    }
+
+    pub fn transform(&self, dataset: &DataSet) -> DataSet {
+        DataSet::new(
+            dataset
+                .get_datas()
+                .into_iter()
+                .map(|dt| {
+                    let mut inputs: Vec<f64> = dt
+                        .inputs
+                        .iter()
+                        .zip(self.fill.iter())
+                        .map(|(v, f)| if v.is_nan() { *f } else { *v })
+                        .collect();
+                    for &i in self.indicators.iter() {
+                        inputs.push(if dt.inputs[i].is_nan() { 1.0 } else { 0.0 });
+                    }
+                    Data {
+                        inputs,
+                        labels: dt.labels,
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Replace missing values of every column with the last known value before it,
+/// `values` are rows in time order. Leading missing values are left as is.
+pub fn forward_fill(values: &mut [Vec<f64>]) {
+    let cols = values.first().map_or(0, |r| r.len());
+    for i in 0..cols {
+        let mut last = f64::NAN;
+        for row in values.iter_mut() {
+            if row[i].is_nan() {
+                row[i] = last;
+            } else {
+                last = row[i];
+            }
+        }
+    }
+}
+
+/// Same as `forward_fill` with the next known value
+pub fn backward_fill(values: &mut [Vec<f64>]) {
+    values.reverse();
+    forward_fill(values);
+    values.reverse();
+}
+
+/// Linearly interpolate missing values of every column between the known values around
+/// them, `x[j]` is the position (e.g. time) of row `j`. Values before the first or after
+/// the last known one take the nearest known value.
+pub fn interpolate(x: &[f64], values: &mut [Vec<f64>]) {
+    if x.len() != values.len() {
+        panic!("every row must have a position");
+    }
+    let cols = values.first().map_or(0, |r| r.len());
+    for i in 0..cols {
+        let known: Vec<usize> = (0..values.len())
+            .filter(|&j| !values[j][i].is_nan())
+            .collect();
+        if known.is_empty() {
+            continue;
+        }
+        for j in 0..values.len() {
+            if !values[j][i].is_nan() {
+                continue;
+            }
+            // first known row after j
+            let next = known.partition_point(|&k| k < j);
+            values[j][i] = if next == 0 {
+                values[known[0]][i]
+            } else if next == known.len() {
+                values[known[next - 1]][i]
+            } else {
+                let (a, b) = (known[next - 1], known[next]);
+                let t = (x[j] - x[a]) / (x[b] - x[a]);
+                values[a][i] + t * (values[b][i] - values[a][i])
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAN: f64 = f64::NAN;
+
+    #[test]
+    fn test_fill_value() {
+        let v = [3.0, NAN, 1.0, 3.0, 5.0];
+        assert_eq!(fill_value(&v, Fill::Mean), 3.0);
+        assert_eq!(fill_value(&v, Fill::Median), 3.0);
+        assert_eq!(fill_value(&[1.0, 2.0, NAN, 2.0, 1.0], Fill::Mode), 1.0);
+        assert_eq!(fill_value(&[4.0, 1.0, 2.0, 3.0], Fill::Median), 2.5);
+        assert_eq!(fill_value(&[NAN], Fill::Mean), 0.0);
+    }
+
+    #[test]
+    fn test_imputer() {
+        let data = |inputs: Vec<f64>| Data {
+            inputs,
+            labels: vec![0.0],
+        };
+        let train = DataSet::new(vec![
+            data(vec![1.0, 10.0]),
+            data(vec![NAN, 20.0]),
+            data(vec![3.0, 30.0]),
+        ]);
+        let valid = DataSet::new(vec![data(vec![NAN, NAN])]);
+
+        let imputer = Imputer::fit(&train, Fill::Mean, true);
+        assert_eq!(imputer.fill, vec![2.0, 20.0]);
+        // only the first feature was missing while fitting
+        assert_eq!(imputer.indicators, vec![0]);
+        assert_eq!(
+            imputer.transform(&train).get_datas()[1].inputs,
+            vec![2.0, 20.0, 1.0]
+        );
+        assert_eq!(
+            imputer.transform(&valid).get_datas()[0].inputs,
+            vec![2.0, 20.0, 1.0]
+        );
+
+        let (t, v) = train.impute_median(&valid);
+        assert_eq!(t.get_feature(0), vec![1.0, 2.0, 3.0]);
+        assert_eq!(v.get_datas()[0].inputs, vec![2.0, 20.0]);
+    }
+
+    #[test]
+    fn test_series_fills() {
+        let rows = || {
+            vec![
+                vec![NAN],
+                vec![1.0],
+                vec![NAN],
+                vec![NAN],
+                vec![4.0],
+                vec![NAN],
+            ]
+        };
+        let col = |v: &[Vec<f64>]| v.iter().map(|r| r[0]).collect::<Vec<f64>>();
+
+        let mut v = rows();
+        forward_fill(&mut v);
+        assert!(v[0][0].is_nan());
+        assert_eq!(col(&v[1..]), vec![1.0, 1.0, 1.0, 4.0, 4.0]);
+
+        let mut v = rows();
+        backward_fill(&mut v);
+        assert_eq!(col(&v[..5]), vec![1.0, 1.0, 4.0, 4.0, 4.0]);
+        assert!(v[5][0].is_nan());
+
+        let mut v = rows();
+        interpolate(&[0.0, 1.0, 2.0, 3.0, 4.0, 5.0], &mut v);
+        assert_eq!(col(&v), vec![1.0, 1.0, 2.0, 3.0, 4.0, 4.0]);
+    }
+}
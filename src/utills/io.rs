@@ -1,28 +1,239 @@
+use super::scaler::Normalization;
 use crate::activator;
+use crate::matrix::Matrix;
 use crate::mlp;
-use serde_json::{json, to_writer_pretty, Value};
+use serde::{Deserialize, Serialize};
+use serde_json::{to_writer_pretty, Value};
 use std::error::Error;
+use std::fmt;
 use std::fs::create_dir;
 use std::fs::File;
 use std::io::Read;
 use std::io::{self, BufRead};
 use std::path::Path;
 
-pub fn save(layers: &Vec<mlp::Layer>, path: String) -> Result<(), Box<dyn Error>> {
-    let mut json: Vec<Value> = vec![];
-
-    for l in layers {
-        json.push(json!({
-            "inputs": l.inputs.len(),
-            "outputs": l.outputs.len(),
-            "w": l.w,
-            "b": l.b,
-            "act": l.act.name
-        }));
+/// Version written by `save_model`, files without a version (a bare array of layers)
+/// are read as version 0.
+pub const MODEL_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum ModelError {
+    UnknownActivation {
+        layer: usize,
+        name: String,
+        params: Vec<f64>,
+    },
+    ShapeMismatch {
+        layer: usize,
+        msg: String,
+    },
+    UnsupportedVersion(u32),
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModelError::UnknownActivation {
+                layer,
+                name,
+                params,
+            } => write!(
+                f,
+                "layer {}: unknown activation {:?} with params {:?}",
+                layer, name, params
+            ),
+            ModelError::ShapeMismatch { layer, msg } => write!(f, "layer {}: {}", layer, msg),
+            ModelError::UnsupportedVersion(v) => write!(
+                f,
+                "model file version {} is newer than supported version {}",
+                v, MODEL_VERSION
+            ),
+            ModelError::Io(e) => write!(f, "{}", e),
+            ModelError::Json(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for ModelError {}
+
+impl From<io::Error> for ModelError {
+    fn from(e: io::Error) -> ModelError {
+        ModelError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ModelError {
+    fn from(e: serde_json::Error) -> ModelError {
+        ModelError::Json(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayerFile {
+    pub inputs: usize,
+    pub outputs: usize,
+    /// outputs x inputs
+    pub w: Vec<Vec<f64>>,
+    pub b: Vec<f64>,
+    pub act: String,
+    #[serde(default)]
+    pub params: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// RFC 3339 time of `ModelFile::new`
+    pub saved_at: String,
+    pub fold: Option<usize>,
+    pub epochs: usize,
+    pub loss: Option<String>,
+    pub train_loss: Option<f64>,
+    pub valid_loss: Option<f64>,
+}
+
+/// Everything needed to rebuild a trained `Net` and feed it data
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelFile {
+    pub version: u32,
+    /// Neurons of every layer, inputs first
+    pub architecture: Vec<usize>,
+    pub layers: Vec<LayerFile>,
+    #[serde(default)]
+    pub normalization: Option<Normalization>,
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+impl ModelFile {
+    pub fn new(net: &mlp::Net) -> ModelFile {
+        ModelFile::from_layers(&net.layers)
+    }
+
+    pub fn from_layers(layers: &[mlp::Layer]) -> ModelFile {
+        let mut architecture: Vec<usize> = vec![];
+        if let Some(l) = layers.first() {
+            architecture.push(l.w.cols());
+        }
+        architecture.extend(layers.iter().map(|l| l.w.rows()));
+
+        ModelFile {
+            version: MODEL_VERSION,
+            architecture,
+            layers: layers
+                .iter()
+                .map(|l| LayerFile {
+                    inputs: l.w.cols(),
+                    outputs: l.w.rows(),
+                    w: l.w.to_rows(),
+                    b: l.b.clone(),
+                    act: l.act.name.clone(),
+                    params: l.act.params.clone(),
+                })
+                .collect(),
+            normalization: None,
+            metadata: Metadata {
+                saved_at: chrono::Utc::now().to_rfc3339(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Check every shape of this file and build its network
+    pub fn to_net(&self) -> Result<mlp::Net, ModelError> {
+        let mismatch = |layer: usize, msg: String| ModelError::ShapeMismatch { layer, msg };
+
+        if self.architecture.len() != self.layers.len() + 1 {
+            return Err(mismatch(
+                0,
+                format!(
+                    "architecture {:?} does not describe {} layers",
+                    self.architecture,
+                    self.layers.len()
+                ),
+            ));
+        }
+
+        let mut layers: Vec<mlp::Layer> = vec![];
+        for (i, l) in self.layers.iter().enumerate() {
+            if l.inputs != self.architecture[i] || l.outputs != self.architecture[i + 1] {
+                return Err(mismatch(
+                    i,
+                    format!(
+                        "{} -> {} does not match architecture {:?}",
+                        l.inputs, l.outputs, self.architecture
+                    ),
+                ));
+            }
+            if l.w.len() != l.outputs || l.w.iter().any(|w_j| w_j.len() != l.inputs) {
+                return Err(mismatch(
+                    i,
+                    format!("weights are not {} x {}", l.outputs, l.inputs),
+                ));
+            }
+            if l.b.len() != l.outputs {
+                return Err(mismatch(
+                    i,
+                    format!("{} biases for {} outputs", l.b.len(), l.outputs),
+                ));
+            }
+
+            let act = match activator::from_name(&l.act, &l.params) {
+                Some(act) => act,
+                None => {
+                    return Err(ModelError::UnknownActivation {
+                        layer: i,
+                        name: l.act.clone(),
+                        params: l.params.clone(),
+                    })
+                }
+            };
+            let mut layer = mlp::Layer::new(l.inputs as u64, l.outputs as u64, 0.0, act);
+            layer.w = Matrix::from_rows(&l.w);
+            layer.b.copy_from_slice(&l.b);
+            layers.push(layer);
+        }
+
+        if let Some(n) = &self.normalization {
+            let features = [
+                (&n.inputs, self.architecture[0], "inputs"),
+                (&n.labels, self.architecture[self.layers.len()], "outputs"),
+            ];
+            for (scaler, size, what) in features {
+                if let Some(s) = scaler {
+                    if s.features() != size {
+                        return Err(mismatch(
+                            0,
+                            format!(
+                                "normalization has {} {} for {} network {}",
+                                s.features(),
+                                what,
+                                size,
+                                what
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(mlp::Net::from_layers(layers))
     }
-    let result = json!(json);
+}
+
+/// Save `layers` as a `ModelFile` without normalization and training metadata
+pub fn save(layers: &[mlp::Layer], path: String) -> Result<(), Box<dyn Error>> {
+    save_model(&ModelFile::from_layers(layers), path)?;
+    Ok(())
+}
+
+pub fn save_model<P>(model: &ModelFile, path: P) -> Result<(), ModelError>
+where
+    P: AsRef<Path>,
+{
     let file = File::create(path)?;
-    to_writer_pretty(&file, &result)?;
+    to_writer_pretty(&file, model)?;
     Ok(())
 }
 
@@ -44,43 +255,42 @@ where
     Ok(contents)
 }
 
-pub fn load<P>(filename: P) -> Result<mlp::Net, Box<dyn Error>>
+pub fn load_model<P>(filename: P) -> Result<ModelFile, ModelError>
 where
     P: AsRef<Path>,
 {
-    let contents = read_file(filename)?;
-
-    let json: Value = serde_json::from_str(&contents)?;
-    let mut layers: Vec<mlp::Layer> = vec![];
-
-    for l in json.as_array().unwrap() {
-        // default layer activation is simeple linear f(x) = x
-        let mut layer = mlp::Layer::new(
-            l["inputs"].as_u64().unwrap(),
-            l["outputs"].as_u64().unwrap(),
-            0.0,
-            activator::linear(),
-        );
-        // setting activation function
-        if l["act"] == "sigmoid" {
-            layer.act = activator::sigmoid();
-        }
+    let file = File::open(filename)?;
+    let json: Value = serde_json::from_reader(io::BufReader::new(file))?;
 
-        // setting weights and bias
-        let w = l["w"].as_array().unwrap();
-        let b = l["b"].as_array().unwrap();
-        for j in 0..w.len() {
-            layer.b[j] = b[j].as_f64().unwrap();
-            let w_j = w[j].as_array().unwrap();
-            for i in 0..w_j.len() {
-                layer.w[j][i] = w_j[i].as_f64().unwrap();
-            }
+    if json.is_array() {
+        // version 0, a bare array of layers
+        let layers: Vec<LayerFile> = serde_json::from_value(json)?;
+        let mut architecture: Vec<usize> = vec![];
+        if let Some(l) = layers.first() {
+            architecture.push(l.inputs);
         }
+        architecture.extend(layers.iter().map(|l| l.outputs));
+        return Ok(ModelFile {
+            version: 0,
+            architecture,
+            layers,
+            normalization: None,
+            metadata: Metadata::default(),
+        });
+    }
 
-        layers.push(layer);
+    let version = json["version"].as_u64().unwrap_or(0) as u32;
+    if version > MODEL_VERSION {
+        return Err(ModelError::UnsupportedVersion(version));
     }
+    Ok(serde_json::from_value(json)?)
+}
 
-    Ok(mlp::Net::from_layers(layers))
+pub fn load<P>(filename: P) -> Result<mlp::Net, ModelError>
+where
+    P: AsRef<Path>,
+{
+    load_model(filename)?.to_net()
 }
 
 /// Check if specify folder exists in models and img folder, if not create it
@@ -97,3 +307,108 @@ pub fn check_dir(folder: &str) -> Result<(String, String), Box<dyn Error>> {
     }
     Ok((models_path, img_path))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utills::scaler::Scaler;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("mlp_io_{}_{}.json", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_save_load_model() {
+        let net = mlp::Net::from_layers(vec![
+            mlp::Layer::new(2, 3, 1.0, activator::leaky_relu(0.05)),
+            mlp::Layer::new(3, 3, 1.0, activator::relu()),
+            mlp::Layer::new(3, 1, 1.0, activator::swish(1.5)),
+        ]);
+        let mut model = ModelFile::new(&net);
+        let x = Matrix::from_rows(&[vec![0.0, 1.0], vec![2.0, -1.0]]);
+        model.normalization = Some(Normalization {
+            inputs: Some(Scaler::standard(&x)),
+            labels: None,
+        });
+        model.metadata.fold = Some(3);
+        model.metadata.epochs = 10;
+        model.metadata.loss = Some("square_err".to_string());
+
+        let path = temp_path("model");
+        save_model(&model, &path).unwrap();
+        let loaded = load_model(&path).unwrap();
+        let loaded_net = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, model);
+        assert_eq!(loaded.architecture, vec![2, 3, 3, 1]);
+        for (a, b) in net.layers.iter().zip(loaded_net.layers.iter()) {
+            assert_eq!(a.act.name, b.act.name);
+            assert_eq!(a.act.params, b.act.params);
+            assert_eq!(a.w, b.w);
+            assert_eq!(a.b, b.b);
+        }
+    }
+
+    #[test]
+    fn test_load_legacy() {
+        let path = temp_path("legacy");
+        std::fs::write(
+            &path,
+            r#"[{"inputs": 2, "outputs": 1, "w": [[1.0, 2.0]], "b": [0.5], "act": "relu"}]"#,
+        )
+        .unwrap();
+        let model = load_model(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(model.version, 0);
+        assert_eq!(model.architecture, vec![2, 1]);
+        let net = model.to_net().unwrap();
+        assert_eq!(net.layers[0].act.name, "relu");
+        assert_eq!(net.layers[0].w.row(0), &[1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let net = mlp::Net::from_layers(vec![mlp::Layer::new(2, 2, 1.0, activator::sigmoid())]);
+
+        let mut model = ModelFile::new(&net);
+        model.layers[0].act = "swishy".into();
+        assert!(matches!(
+            model.to_net(),
+            Err(ModelError::UnknownActivation { layer: 0, .. })
+        ));
+
+        let mut model = ModelFile::new(&net);
+        model.layers[0].w[1].pop();
+        assert!(matches!(
+            model.to_net(),
+            Err(ModelError::ShapeMismatch { layer: 0, .. })
+        ));
+
+        let mut model = ModelFile::new(&net);
+        model.architecture = vec![2, 3];
+        assert!(matches!(
+            model.to_net(),
+            Err(ModelError::ShapeMismatch { .. })
+        ));
+
+        let mut model = ModelFile::new(&net);
+        model.normalization = Some(Normalization {
+            inputs: Some(Scaler::minmax(&Matrix::zeros(1, 3))),
+            labels: None,
+        });
+        assert!(matches!(
+            model.to_net(),
+            Err(ModelError::ShapeMismatch { .. })
+        ));
+
+        let mut model = ModelFile::new(&net);
+        model.version = MODEL_VERSION + 1;
+        let path = temp_path("version");
+        save_model(&model, &path).unwrap();
+        let result = load_model(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(ModelError::UnsupportedVersion(_))));
+    }
+}
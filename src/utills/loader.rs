@@ -0,0 +1,337 @@
+//! Build a `DataSet` from any delimited (CSV/TSV) file without writing a record struct.
+use super::data::{Data, DataSet};
+use std::error::Error;
+
+/// A column picked by its header name or its 0-based index
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+    Name(String),
+    Index(usize),
+}
+
+impl From<&str> for Column {
+    fn from(name: &str) -> Column {
+        Column::Name(name.to_string())
+    }
+}
+
+impl From<usize> for Column {
+    fn from(i: usize) -> Column {
+        Column::Index(i)
+    }
+}
+
+/// What to do with a row holding a missing value (empty cell or the sentinel)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Missing {
+    /// Fail to load
+    Error,
+    /// Skip the whole row
+    DropRow,
+    /// Keep the row with `f64::NAN` in place of the value
+    Nan,
+}
+
+/// A data set and the `meta` columns of every sample, as text
+pub type Loaded = (DataSet, Vec<Vec<String>>);
+
+/// Loader for a delimited file, e.g.
+///
+/// `Loader::new("data/wdbc.txt").header(false).inputs(2..32).label(1).classes(&["B", "M"])`
+pub struct Loader {
+    path: String,
+    delimiter: u8,
+    header: bool,
+    inputs: Vec<Column>,
+    labels: Vec<Column>,
+    meta: Vec<Column>,
+    classes: Option<Vec<String>>,
+    one_hot: bool,
+    sentinel: Option<f64>,
+    missing: Missing,
+}
+
+impl Loader {
+    /// Comma delimited with a header row by default
+    pub fn new(path: &str) -> Loader {
+        Loader {
+            path: path.to_string(),
+            delimiter: b',',
+            header: true,
+            inputs: vec![],
+            labels: vec![],
+            meta: vec![],
+            classes: None,
+            one_hot: false,
+            sentinel: None,
+            missing: Missing::Error,
+        }
+    }
+
+    pub fn delimiter(mut self, delimiter: u8) -> Loader {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Tab delimited
+    pub fn tsv(self) -> Loader {
+        self.delimiter(b'\t')
+    }
+
+    /// Whether the first row holds column names, required to pick columns by name
+    pub fn header(mut self, header: bool) -> Loader {
+        self.header = header;
+        self
+    }
+
+    pub fn inputs<C, I>(mut self, columns: I) -> Loader
+    where
+        C: Into<Column>,
+        I: IntoIterator<Item = C>,
+    {
+        self.inputs = columns.into_iter().map(|c| c.into()).collect();
+        self
+    }
+
+    pub fn labels<C, I>(mut self, columns: I) -> Loader
+    where
+        C: Into<Column>,
+        I: IntoIterator<Item = C>,
+    {
+        self.labels = columns.into_iter().map(|c| c.into()).collect();
+        self
+    }
+
+    pub fn label<C: Into<Column>>(self, column: C) -> Loader {
+        self.labels([column])
+    }
+
+    /// Columns kept as text next to every sample by `load_with_meta`, e.g. dates
+    pub fn meta<C, I>(mut self, columns: I) -> Loader
+    where
+        C: Into<Column>,
+        I: IntoIterator<Item = C>,
+    {
+        self.meta = columns.into_iter().map(|c| c.into()).collect();
+        self
+    }
+
+    /// Map the text of each label column to the index of its class, or to a one-hot
+    /// vector when `one_hot`
+    pub fn classes(mut self, classes: &[&str]) -> Loader {
+        self.classes = Some(classes.iter().map(|c| c.to_string()).collect());
+        self
+    }
+
+    pub fn one_hot(mut self, one_hot: bool) -> Loader {
+        self.one_hot = one_hot;
+        self
+    }
+
+    /// Treat `value` like an empty cell, e.g. AirQuality's `-200`
+    pub fn sentinel(mut self, value: f64) -> Loader {
+        self.sentinel = Some(value);
+        self
+    }
+
+    pub fn missing(mut self, policy: Missing) -> Loader {
+        self.missing = policy;
+        self
+    }
+
+    pub fn load(&self) -> Result<DataSet, Box<dyn Error>> {
+        Ok(self.load_with_meta()?.0)
+    }
+
+    /// Load the data set and the `meta` columns of every kept sample
+    pub fn load_with_meta(&self) -> Result<Loaded, Box<dyn Error>> {
+        if self.inputs.is_empty() {
+            return Err("no input columns selected".into());
+        }
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .has_headers(self.header)
+            .from_path(&self.path)?;
+        let names: Vec<String> = if self.header {
+            reader
+                .headers()?
+                .iter()
+                .map(|h| h.trim().to_string())
+                .collect()
+        } else {
+            vec![]
+        };
+        let resolve = |columns: &[Column]| -> Result<Vec<usize>, Box<dyn Error>> {
+            columns.iter().map(|c| index(c, &names)).collect()
+        };
+        let inputs = resolve(&self.inputs)?;
+        let labels = resolve(&self.labels)?;
+        let meta = resolve(&self.meta)?;
+
+        let mut datas: Vec<Data> = vec![];
+        let mut metas: Vec<Vec<String>> = vec![];
+        'rows: for (n, record) in reader.records().enumerate() {
+            let record = record?;
+            let cell = |i: usize| -> Result<&str, Box<dyn Error>> {
+                match record.get(i) {
+                    Some(v) => Ok(v.trim()),
+                    None => Err(format!("row {}: no column {}", n + 1, i).into()),
+                }
+            };
+
+            let mut row_inputs: Vec<f64> = vec![];
+            for &i in inputs.iter() {
+                match self.value(cell(i)?, n)? {
+                    Some(v) => row_inputs.push(v),
+                    None => continue 'rows,
+                }
+            }
+
+            let mut row_labels: Vec<f64> = vec![];
+            for &i in labels.iter() {
+                let text = cell(i)?;
+                match &self.classes {
+                    Some(classes) => {
+                        let class = match classes.iter().position(|c| c == text) {
+                            Some(class) => class,
+                            None => {
+                                return Err(
+                                    format!("row {}: unknown class {:?}", n + 1, text).into()
+                                )
+                            }
+                        };
+                        if self.one_hot {
+                            let mut v = vec![0.0; classes.len()];
+                            v[class] = 1.0;
+                            row_labels.extend(v);
+                        } else {
+                            row_labels.push(class as f64);
+                        }
+                    }
+                    None => match self.value(text, n)? {
+                        Some(v) => row_labels.push(v),
+                        None => continue 'rows,
+                    },
+                }
+            }
+
+            metas.push(
+                meta.iter()
+                    .map(|&i| cell(i).map(|v| v.to_string()))
+                    .collect::<Result<Vec<String>, Box<dyn Error>>>()?,
+            );
+            datas.push(Data {
+                inputs: row_inputs,
+                labels: row_labels,
+            });
+        }
+        Ok((DataSet::new(datas), metas))
+    }
+
+    /// Parse a numeric cell, `None` when its row should be dropped
+    fn value(&self, text: &str, row: usize) -> Result<Option<f64>, Box<dyn Error>> {
+        let v: Option<f64> = if text.is_empty() {
+            None
+        } else {
+            match text.parse::<f64>() {
+                Ok(v) if Some(v) == self.sentinel => None,
+                Ok(v) => Some(v),
+                Err(_) => return Err(format!("row {}: {:?} is not a number", row + 1, text).into()),
+            }
+        };
+        match (v, self.missing) {
+            (Some(v), _) => Ok(Some(v)),
+            (None, Missing::DropRow) => Ok(None),
+            (None, Missing::Nan) => Ok(Some(f64::NAN)),
+            (None, Missing::Error) => Err(format!("row {}: missing value", row + 1).into()),
+        }
+    }
+}
+
+fn index(column: &Column, names: &[String]) -> Result<usize, Box<dyn Error>> {
+    match column {
+        Column::Index(i) => Ok(*i),
+        Column::Name(name) => match names.iter().position(|n| n == name) {
+            Some(i) => Ok(i),
+            None if names.is_empty() => {
+                Err(format!("column {:?} picked by name without a header", name).into())
+            }
+            None => Err(format!("no column named {:?}", name).into()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_temp(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("mlp_loader_{}_{}", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_columns_and_sentinel() -> Result<(), Box<dyn Error>> {
+        let path = write_temp(
+            "air.csv",
+            "date,a,b,y\n1/1,1,2,3\n1/2,-200,5,6\n1/3,7,,9\n1/4,10,11,12\n",
+        );
+        let loader = Loader::new(&path)
+            .inputs(["b", "a"])
+            .label("y")
+            .sentinel(-200.0);
+
+        assert!(loader.load().is_err());
+        let (dt, meta) = loader
+            .missing(Missing::DropRow)
+            .meta([0])
+            .load_with_meta()?;
+        assert_eq!(dt.len(), 2);
+        assert_eq!(dt.get_datas()[1].inputs, vec![11.0, 10.0]);
+        assert_eq!(dt.get_datas()[1].labels, vec![12.0]);
+        assert_eq!(meta, vec![vec!["1/1".to_string()], vec!["1/4".to_string()]]);
+
+        let dt = Loader::new(&path)
+            .inputs([1, 2])
+            .label(3)
+            .sentinel(-200.0)
+            .missing(Missing::Nan)
+            .load()?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(dt.len(), 4);
+        assert!(dt.get_datas()[1].inputs[0].is_nan());
+        assert!(dt.get_datas()[2].inputs[1].is_nan());
+        Ok(())
+    }
+
+    #[test]
+    fn test_classes() -> Result<(), Box<dyn Error>> {
+        let path = write_temp("classes.tsv", "1\tM\t0.5\n2\tB\t0.25\n");
+        let loader = Loader::new(&path)
+            .tsv()
+            .header(false)
+            .inputs([2])
+            .label(1)
+            .classes(&["B", "M"]);
+
+        let dt = loader.load()?;
+        assert_eq!(dt.get_label(0), vec![1.0, 0.0]);
+        assert_eq!(dt.get_feature(0), vec![0.5, 0.25]);
+
+        let dt = loader.one_hot(true).load()?;
+        assert_eq!(dt.get_datas()[0].labels, vec![0.0, 1.0]);
+
+        let err = Loader::new(&path).tsv().header(false).inputs(["a"]).load();
+        assert!(err.is_err());
+        let err = Loader::new(&path)
+            .tsv()
+            .header(false)
+            .inputs([2])
+            .label(1)
+            .load();
+        std::fs::remove_file(&path)?;
+        assert!(err.is_err());
+        Ok(())
+    }
+}
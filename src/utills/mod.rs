@@ -1,3 +1,10 @@
 pub mod data;
+pub mod gradcheck;
 pub mod graph;
+pub mod impute;
 pub mod io;
+pub mod loader;
+pub mod parallel;
+pub mod scaler;
+pub mod split;
+pub mod timeseries;
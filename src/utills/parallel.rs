@@ -0,0 +1,69 @@
+//! Evaluation of many parameter vectors of one net shape across threads.
+use std::thread;
+
+use crate::mlp::Net;
+
+/// Number of threads the machine can run at once, 1 if it is unknown
+pub fn available_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// `fitness` of `net` loaded with every parameter vector of `params`, in order.
+/// Each of the `threads` workers owns a clone of `net` and evaluates a contiguous
+/// chunk, the result does not depend on the number of threads.
+pub fn evaluate<P: AsRef<[f64]> + Sync, T: Send>(
+    net: &Net,
+    params: &[P],
+    threads: usize,
+    fitness: &(dyn Fn(&mut Net) -> T + Sync),
+) -> Vec<T> {
+    if threads == 0 {
+        panic!("threads must be at least 1");
+    }
+    let eval = |chunk: &[P]| -> Vec<T> {
+        let mut net = net.clone();
+        chunk
+            .iter()
+            .map(|p| {
+                net.set_params(p.as_ref());
+                fitness(&mut net)
+            })
+            .collect()
+    };
+    if threads == 1 || params.len() < 2 {
+        return eval(params);
+    }
+
+    let eval = &eval;
+    let chunk_size = params.len().div_ceil(threads);
+    thread::scope(|s| {
+        let workers: Vec<_> = params
+            .chunks(chunk_size)
+            .map(|chunk| s.spawn(move || eval(chunk)))
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().expect("fitness evaluation panicked"))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{activator, mlp::Layer};
+
+    #[test]
+    fn test_evaluate() {
+        let net = Net::from_layers(vec![Layer::new(2, 1, 1.0, activator::linear())]);
+        let params: Vec<Vec<f64>> = (0..7).map(|i| vec![i as f64, 1.0, 0.5]).collect();
+        let output = |net: &mut Net| net.forward(&[1.0, 2.0])[0];
+
+        let expected: Vec<f64> = (0..7).map(|i| i as f64 + 2.5).collect();
+        assert_eq!(evaluate(&net, &params, 1, &output), expected);
+        assert_eq!(evaluate(&net, &params, 3, &output), expected);
+        assert_eq!(evaluate(&net, &params, 16, &output), expected);
+        // the given net is left as is
+        assert_eq!(net.get_params()[2], 1.0);
+    }
+}
@@ -0,0 +1,327 @@
+//! Per-feature scalers fit on a training set and saved with the model by `utills::io`,
+//! so a loaded network can be fed raw inputs and give raw outputs.
+use super::data::{Data, DataSet};
+use crate::matrix::Matrix;
+use crate::mlp::Net;
+use serde::{Deserialize, Serialize};
+
+pub trait Scale {
+    fn transform(&self, x: &[f64]) -> Vec<f64>;
+    fn inverse_transform(&self, x: &[f64]) -> Vec<f64>;
+}
+
+fn columns(x: &Matrix) -> Vec<Vec<f64>> {
+    (0..x.cols())
+        .map(|i| x.iter_rows().map(|r| r[i]).collect())
+        .collect()
+}
+
+/// Constant features have no spread, leave them unscaled instead of dividing by 0
+fn nonzero(scale: f64) -> f64 {
+    if scale == 0.0 {
+        1.0
+    } else {
+        scale
+    }
+}
+
+/// (x - mean) / std
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StandardScaler {
+    pub mean: Vec<f64>,
+    pub std: Vec<f64>,
+}
+
+impl StandardScaler {
+    pub fn fit(x: &Matrix) -> StandardScaler {
+        let mut mean: Vec<f64> = vec![];
+        let mut std: Vec<f64> = vec![];
+        for c in columns(x) {
+            let m = super::data::mean(&c);
+            std.push(nonzero(super::data::std(&c, m)));
+            mean.push(m);
+        }
+        StandardScaler { mean, std }
+    }
+}
+
+impl Scale for StandardScaler {
+    fn transform(&self, x: &[f64]) -> Vec<f64> {
+        x.iter()
+            .zip(self.mean.iter().zip(self.std.iter()))
+            .map(|(v, (m, s))| (v - m) / s)
+            .collect()
+    }
+
+    fn inverse_transform(&self, x: &[f64]) -> Vec<f64> {
+        x.iter()
+            .zip(self.mean.iter().zip(self.std.iter()))
+            .map(|(v, (m, s))| v * s + m)
+            .collect()
+    }
+}
+
+/// (x - min) / (max - min), maps the training set into [0, 1]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MinMaxScaler {
+    pub min: Vec<f64>,
+    pub max: Vec<f64>,
+}
+
+impl MinMaxScaler {
+    pub fn fit(x: &Matrix) -> MinMaxScaler {
+        let cols = columns(x);
+        MinMaxScaler {
+            min: cols.iter().map(super::data::min).collect(),
+            max: cols.iter().map(super::data::max).collect(),
+        }
+    }
+}
+
+impl Scale for MinMaxScaler {
+    fn transform(&self, x: &[f64]) -> Vec<f64> {
+        x.iter()
+            .zip(self.min.iter().zip(self.max.iter()))
+            .map(|(v, (min, max))| (v - min) / nonzero(max - min))
+            .collect()
+    }
+
+    fn inverse_transform(&self, x: &[f64]) -> Vec<f64> {
+        x.iter()
+            .zip(self.min.iter().zip(self.max.iter()))
+            .map(|(v, (min, max))| v * nonzero(max - min) + min)
+            .collect()
+    }
+}
+
+/// (x - median) / IQR, less sensitive to outliers than `StandardScaler`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RobustScaler {
+    pub median: Vec<f64>,
+    pub iqr: Vec<f64>,
+}
+
+/// Linearly interpolated `q` quantile of sorted `v`
+fn quantile(v: &[f64], q: f64) -> f64 {
+    let pos = q * (v.len() - 1) as f64;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+    v[lo] + (v[hi] - v[lo]) * (pos - lo as f64)
+}
+
+impl RobustScaler {
+    pub fn fit(x: &Matrix) -> RobustScaler {
+        let mut median: Vec<f64> = vec![];
+        let mut iqr: Vec<f64> = vec![];
+        for mut c in columns(x) {
+            c.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            median.push(quantile(&c, 0.5));
+            iqr.push(nonzero(quantile(&c, 0.75) - quantile(&c, 0.25)));
+        }
+        RobustScaler { median, iqr }
+    }
+}
+
+impl Scale for RobustScaler {
+    fn transform(&self, x: &[f64]) -> Vec<f64> {
+        x.iter()
+            .zip(self.median.iter().zip(self.iqr.iter()))
+            .map(|(v, (m, s))| (v - m) / s)
+            .collect()
+    }
+
+    fn inverse_transform(&self, x: &[f64]) -> Vec<f64> {
+        x.iter()
+            .zip(self.median.iter().zip(self.iqr.iter()))
+            .map(|(v, (m, s))| v * s + m)
+            .collect()
+    }
+}
+
+/// Any of the scalers, in a form that can be saved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Scaler {
+    Standard(StandardScaler),
+    MinMax(MinMaxScaler),
+    Robust(RobustScaler),
+}
+
+/// Fit a scaler on the rows of a matrix, e.g. `Scaler::standard`
+pub type FitScaler = fn(&Matrix) -> Scaler;
+
+impl Scaler {
+    pub fn standard(x: &Matrix) -> Scaler {
+        Scaler::Standard(StandardScaler::fit(x))
+    }
+
+    pub fn minmax(x: &Matrix) -> Scaler {
+        Scaler::MinMax(MinMaxScaler::fit(x))
+    }
+
+    pub fn robust(x: &Matrix) -> Scaler {
+        Scaler::Robust(RobustScaler::fit(x))
+    }
+
+    /// Number of features this scaler was fit on
+    pub fn features(&self) -> usize {
+        match self {
+            Scaler::Standard(s) => s.mean.len(),
+            Scaler::MinMax(s) => s.min.len(),
+            Scaler::Robust(s) => s.median.len(),
+        }
+    }
+
+    fn inner(&self) -> &dyn Scale {
+        match self {
+            Scaler::Standard(s) => s,
+            Scaler::MinMax(s) => s,
+            Scaler::Robust(s) => s,
+        }
+    }
+}
+
+impl Scale for Scaler {
+    fn transform(&self, x: &[f64]) -> Vec<f64> {
+        self.inner().transform(x)
+    }
+
+    fn inverse_transform(&self, x: &[f64]) -> Vec<f64> {
+        self.inner().inverse_transform(x)
+    }
+}
+
+/// Scalers of the inputs and labels a network was trained with
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Normalization {
+    pub inputs: Option<Scaler>,
+    pub labels: Option<Scaler>,
+}
+
+impl Normalization {
+    /// Fit the given scalers on `training_set`
+    pub fn fit(
+        training_set: &DataSet,
+        inputs: Option<FitScaler>,
+        labels: Option<FitScaler>,
+    ) -> Normalization {
+        Normalization {
+            inputs: inputs.map(|f| f(&training_set.inputs_matrix())),
+            labels: labels.map(|f| f(&training_set.labels_matrix())),
+        }
+    }
+
+    pub fn transform(&self, dataset: &DataSet) -> DataSet {
+        DataSet::new(
+            dataset
+                .get_datas()
+                .iter()
+                .map(|dt| Data {
+                    inputs: match &self.inputs {
+                        Some(s) => s.transform(&dt.inputs),
+                        None => dt.inputs.clone(),
+                    },
+                    labels: match &self.labels {
+                        Some(s) => s.transform(&dt.labels),
+                        None => dt.labels.clone(),
+                    },
+                })
+                .collect(),
+        )
+    }
+
+    /// Forward raw `inputs` (one sample per row) and return outputs in raw label units
+    pub fn predict(&self, net: &Net, inputs: &Matrix) -> Matrix {
+        let mut x = inputs.clone();
+        if let Some(s) = &self.inputs {
+            for r in 0..x.rows() {
+                let scaled = s.transform(x.row(r));
+                x.row_mut(r).copy_from_slice(&scaled);
+            }
+        }
+        let mut result = net.forward_batch(&x);
+        if let Some(s) = &self.labels {
+            for r in 0..result.rows() {
+                let raw = s.inverse_transform(result.row(r));
+                result.row_mut(r).copy_from_slice(&raw);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activator;
+    use crate::mlp::Layer;
+
+    fn samples() -> Matrix {
+        Matrix::from_rows(&[
+            vec![1.0, 10.0, 5.0],
+            vec![2.0, 20.0, 5.0],
+            vec![3.0, 30.0, 5.0],
+            vec![4.0, 1000.0, 5.0],
+        ])
+    }
+
+    #[test]
+    fn test_scalers() {
+        let x = samples();
+
+        let s = StandardScaler::fit(&x);
+        assert_eq!(s.mean, vec![2.5, 265.0, 5.0]);
+        // constant feature is left as is
+        assert_eq!(s.std[2], 1.0);
+
+        let s = MinMaxScaler::fit(&x);
+        assert_eq!(s.transform(&[4.0, 10.0, 5.0]), vec![1.0, 0.0, 0.0]);
+
+        let s = RobustScaler::fit(&x);
+        assert_eq!(s.median, vec![2.5, 25.0, 5.0]);
+        assert_eq!(s.iqr[0], 1.5);
+
+        for s in [Scaler::standard(&x), Scaler::minmax(&x), Scaler::robust(&x)] {
+            assert_eq!(s.features(), 3);
+            for r in x.iter_rows() {
+                let back = s.inverse_transform(&s.transform(r));
+                for (a, b) in back.iter().zip(r.iter()) {
+                    assert!((a - b).abs() < 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_serialize() {
+        let s = Scaler::robust(&samples());
+        let json = serde_json::to_string(&s).unwrap();
+        assert!(json.contains("\"kind\":\"robust\""));
+        assert_eq!(serde_json::from_str::<Scaler>(&json).unwrap(), s);
+    }
+
+    #[test]
+    fn test_predict_raw() {
+        let dataset = DataSet::new(
+            (0..10)
+                .map(|i| Data {
+                    inputs: vec![i as f64 * 100.0],
+                    labels: vec![i as f64 * 3.0 + 50.0],
+                })
+                .collect(),
+        );
+        let norm = Normalization::fit(&dataset, Some(Scaler::minmax), Some(Scaler::standard));
+        let scaled = norm.transform(&dataset);
+        assert_eq!(scaled.get_feature(0)[9], 1.0);
+
+        // identity network, raw labels come back from scaled inputs
+        let mut net = Net::from_layers(vec![Layer::new(1, 1, 0.0, activator::linear())]);
+        net.layers[0].w[(0, 0)] = 1.0;
+        let raw = norm.predict(&net, &dataset.inputs_matrix());
+        let expected = norm
+            .labels
+            .as_ref()
+            .unwrap()
+            .inverse_transform(&scaled.get_feature(0)[3..4]);
+        assert!((raw[(3, 0)] - expected[0]).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,348 @@
+//! Seeded ways of splitting a `DataSet` into training and validation folds.
+use super::data::DataSet;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+/// Indices of the training and validation samples of one fold
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fold {
+    pub train: Vec<usize>,
+    pub valid: Vec<usize>,
+}
+
+pub trait Splitter {
+    fn split(&self, dataset: &DataSet) -> Vec<Fold>;
+}
+
+/// Turn validation index sets into folds, every other index is used for training
+fn complement(n: usize, valid_sets: Vec<Vec<usize>>) -> Vec<Fold> {
+    valid_sets
+        .into_iter()
+        .map(|mut valid| {
+            valid.sort_unstable();
+            let mut in_valid = vec![false; n];
+            valid.iter().for_each(|&i| in_valid[i] = true);
+            Fold {
+                train: (0..n).filter(|&i| !in_valid[i]).collect(),
+                valid,
+            }
+        })
+        .collect()
+}
+
+fn shuffled(n: usize, rng: &mut StdRng) -> Vec<usize> {
+    let mut idx: Vec<usize> = (0..n).collect();
+    idx.shuffle(rng);
+    idx
+}
+
+/// Class of a sample, the argmax of one-hot labels or the rounded single label
+pub fn class_of(labels: &[f64]) -> usize {
+    if labels.len() == 1 {
+        labels[0].round().max(0.0) as usize
+    } else {
+        labels
+            .iter()
+            .enumerate()
+            .fold((0, f64::MIN), |m, (i, &v)| if v > m.1 { (i, v) } else { m })
+            .0
+    }
+}
+
+/// `k` folds of (almost) equal size from shuffled samples
+pub struct KFold {
+    pub k: usize,
+    pub seed: u64,
+}
+
+impl KFold {
+    pub fn new(k: usize, seed: u64) -> KFold {
+        KFold { k, seed }
+    }
+}
+
+impl Splitter for KFold {
+    fn split(&self, dataset: &DataSet) -> Vec<Fold> {
+        let n = dataset.len();
+        if self.k < 2 || self.k > n {
+            panic!("k must be in range [2, dataset size]");
+        }
+        let idx = shuffled(n, &mut StdRng::seed_from_u64(self.seed));
+        let mut valid_sets: Vec<Vec<usize>> = vec![vec![]; self.k];
+        for (j, i) in idx.into_iter().enumerate() {
+            valid_sets[j % self.k].push(i);
+        }
+        complement(n, valid_sets)
+    }
+}
+
+/// `k` folds keeping the class proportions of the whole set, see `class_of`
+pub struct StratifiedKFold {
+    pub k: usize,
+    pub seed: u64,
+}
+
+impl StratifiedKFold {
+    pub fn new(k: usize, seed: u64) -> StratifiedKFold {
+        StratifiedKFold { k, seed }
+    }
+}
+
+impl Splitter for StratifiedKFold {
+    fn split(&self, dataset: &DataSet) -> Vec<Fold> {
+        let n = dataset.len();
+        if self.k < 2 || self.k > n {
+            panic!("k must be in range [2, dataset size]");
+        }
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut classes: Vec<Vec<usize>> = vec![];
+        for (i, dt) in dataset.get_datas().iter().enumerate() {
+            let c = class_of(&dt.labels);
+            if c >= classes.len() {
+                classes.resize(c + 1, vec![]);
+            }
+            classes[c].push(i);
+        }
+
+        // deal every class in turn, continuing from the fold the last class ended on
+        let mut valid_sets: Vec<Vec<usize>> = vec![vec![]; self.k];
+        let mut j = 0;
+        for mut members in classes {
+            members.shuffle(&mut rng);
+            for i in members {
+                valid_sets[j % self.k].push(i);
+                j += 1;
+            }
+        }
+        complement(n, valid_sets)
+    }
+}
+
+/// `KFold` (or `StratifiedKFold` when `stratified`) run `repeats` times with a new
+/// shuffle each time
+pub struct RepeatedKFold {
+    pub k: usize,
+    pub repeats: usize,
+    pub stratified: bool,
+    pub seed: u64,
+}
+
+impl RepeatedKFold {
+    pub fn new(k: usize, repeats: usize, seed: u64) -> RepeatedKFold {
+        RepeatedKFold {
+            k,
+            repeats,
+            stratified: false,
+            seed,
+        }
+    }
+}
+
+impl Splitter for RepeatedKFold {
+    fn split(&self, dataset: &DataSet) -> Vec<Fold> {
+        let mut folds: Vec<Fold> = vec![];
+        for r in 0..self.repeats {
+            let seed = self.seed.wrapping_add(r as u64);
+            if self.stratified {
+                folds.extend(StratifiedKFold::new(self.k, seed).split(dataset));
+            } else {
+                folds.extend(KFold::new(self.k, seed).split(dataset));
+            }
+        }
+        folds
+    }
+}
+
+/// One fold per sample, validated on that sample alone
+pub struct LeaveOneOut;
+
+impl Splitter for LeaveOneOut {
+    fn split(&self, dataset: &DataSet) -> Vec<Fold> {
+        complement(dataset.len(), (0..dataset.len()).map(|i| vec![i]).collect())
+    }
+}
+
+/// `k` folds where all samples of a group land in the same fold, so a group is never
+/// both trained and validated on. `groups[i]` is the group of sample `i`.
+pub struct GroupKFold {
+    pub k: usize,
+    pub groups: Vec<usize>,
+    pub seed: u64,
+}
+
+impl GroupKFold {
+    pub fn new(k: usize, groups: Vec<usize>, seed: u64) -> GroupKFold {
+        GroupKFold { k, groups, seed }
+    }
+}
+
+impl Splitter for GroupKFold {
+    fn split(&self, dataset: &DataSet) -> Vec<Fold> {
+        let n = dataset.len();
+        if self.groups.len() != n {
+            panic!("every sample must have a group");
+        }
+        let mut members: Vec<Vec<usize>> = vec![];
+        for (i, &g) in self.groups.iter().enumerate() {
+            if g >= members.len() {
+                members.resize(g + 1, vec![]);
+            }
+            members[g].push(i);
+        }
+        members.retain(|m| !m.is_empty());
+        if self.k < 2 || self.k > members.len() {
+            panic!("k must be in range [2, number of groups]");
+        }
+
+        // largest groups first into the smallest fold, ties broken by the seed
+        members.shuffle(&mut StdRng::seed_from_u64(self.seed));
+        members.sort_by_key(|m| std::cmp::Reverse(m.len()));
+        let mut valid_sets: Vec<Vec<usize>> = vec![vec![]; self.k];
+        for m in members {
+            let smallest = (0..self.k).min_by_key(|&j| valid_sets[j].len()).unwrap();
+            valid_sets[smallest].extend(m);
+        }
+        complement(n, valid_sets)
+    }
+}
+
+/// Single shuffled train/validation/test split, `valid` and `test` are fractions of
+/// the set. As a `Splitter` it gives one fold without the test samples.
+pub struct Holdout {
+    pub valid: f64,
+    pub test: f64,
+    pub seed: u64,
+}
+
+impl Holdout {
+    pub fn new(valid: f64, test: f64, seed: u64) -> Holdout {
+        if valid < 0.0 || test < 0.0 || valid + test >= 1.0 {
+            panic!("valid and test must be positive and leave samples for training");
+        }
+        Holdout { valid, test, seed }
+    }
+
+    /// Indices of the (train, valid, test) samples
+    pub fn indices(&self, n: usize) -> (Vec<usize>, Vec<usize>, Vec<usize>) {
+        let idx = shuffled(n, &mut StdRng::seed_from_u64(self.seed));
+        let n_test = (self.test * n as f64).round() as usize;
+        let n_valid = (self.valid * n as f64).round() as usize;
+        let mut test = idx[..n_test].to_vec();
+        let mut valid = idx[n_test..n_test + n_valid].to_vec();
+        let mut train = idx[n_test + n_valid..].to_vec();
+        test.sort_unstable();
+        valid.sort_unstable();
+        train.sort_unstable();
+        (train, valid, test)
+    }
+
+    /// Split `dataset` into (training, validation, test) sets
+    pub fn split3(&self, dataset: &DataSet) -> (DataSet, DataSet, DataSet) {
+        let (train, valid, test) = self.indices(dataset.len());
+        (
+            dataset.subset(&train),
+            dataset.subset(&valid),
+            dataset.subset(&test),
+        )
+    }
+}
+
+impl Splitter for Holdout {
+    fn split(&self, dataset: &DataSet) -> Vec<Fold> {
+        let (train, valid, _) = self.indices(dataset.len());
+        vec![Fold { train, valid }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utills::data::Data;
+
+    fn dataset(n: usize) -> DataSet {
+        DataSet::new(
+            (0..n)
+                .map(|i| Data {
+                    inputs: vec![i as f64],
+                    // one sample in four is class 1
+                    labels: vec![if i % 4 == 0 { 1.0 } else { 0.0 }],
+                })
+                .collect(),
+        )
+    }
+
+    /// Every sample is validated exactly once and never trained on in the same fold
+    fn assert_partition(folds: &[Fold], n: usize) {
+        let mut seen = vec![0; n];
+        for f in folds {
+            assert_eq!(f.train.len() + f.valid.len(), n);
+            for i in f.valid.iter() {
+                assert!(!f.train.contains(i));
+                seen[*i] += 1;
+            }
+        }
+        assert!(seen.iter().all(|&s| s == 1));
+    }
+
+    #[test]
+    fn test_kfold() {
+        let dt = dataset(23);
+        let folds = KFold::new(5, 7).split(&dt);
+        assert_eq!(folds.len(), 5);
+        assert_partition(&folds, 23);
+        assert!(folds
+            .iter()
+            .all(|f| f.valid.len() == 4 || f.valid.len() == 5));
+        // same seed, same folds
+        assert_eq!(folds, KFold::new(5, 7).split(&dt));
+        assert_ne!(folds, KFold::new(5, 8).split(&dt));
+    }
+
+    #[test]
+    fn test_stratified() {
+        let dt = dataset(40);
+        let folds = StratifiedKFold::new(5, 1).split(&dt);
+        assert_partition(&folds, 40);
+        for f in folds.iter() {
+            let positives = f.valid.iter().filter(|&&i| i % 4 == 0).count();
+            assert_eq!(positives, 2);
+        }
+        assert_eq!(class_of(&[0.1, 0.7, 0.2]), 1);
+    }
+
+    #[test]
+    fn test_repeated_and_loo() {
+        let dt = dataset(10);
+        let folds = RepeatedKFold::new(2, 3, 0).split(&dt);
+        assert_eq!(folds.len(), 6);
+        assert_partition(&folds[2..4], 10);
+
+        let folds = LeaveOneOut.split(&dt);
+        assert_eq!(folds.len(), 10);
+        assert_partition(&folds, 10);
+    }
+
+    #[test]
+    fn test_group_kfold() {
+        let dt = dataset(12);
+        let groups: Vec<usize> = (0..12).map(|i| i / 3).collect();
+        let folds = GroupKFold::new(2, groups.clone(), 3).split(&dt);
+        assert_partition(&folds, 12);
+        for f in folds.iter() {
+            for i in f.valid.iter() {
+                assert!(f.train.iter().all(|j| groups[*j] != groups[*i]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_holdout() {
+        let dt = dataset(20);
+        let (train, valid, test) = Holdout::new(0.2, 0.1, 5).split3(&dt);
+        assert_eq!((train.len(), valid.len(), test.len()), (14, 4, 2));
+        let folds = Holdout::new(0.2, 0.1, 5).split(&dt);
+        assert_eq!(folds.len(), 1);
+        assert_eq!(folds[0].valid.len(), 4);
+    }
+}
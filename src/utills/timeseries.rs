@@ -0,0 +1,241 @@
+//! Lagged windows over a timestamped series and cross-validation that never trains on
+//! the future of its validation samples.
+use super::data::{Data, DataSet};
+use super::split::{Fold, Splitter};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+
+/// Observations in time order, `inputs[i]` and `targets[i]` are taken at `times[i]`.
+/// Missing timestamps are fine, windows needing them are skipped. Missing (`NaN`) inputs
+/// are kept, fill them with `impute` functions or on each fold.
+pub struct TimeSeries {
+    pub times: Vec<DateTime<Utc>>,
+    pub inputs: Vec<Vec<f64>>,
+    pub targets: Vec<Vec<f64>>,
+}
+
+/// How `TimeSeries::windows` builds samples
+#[derive(Debug, Clone)]
+pub struct Windowing {
+    /// Time between two observations
+    pub step: Duration,
+    /// Steps back of the inputs of a sample, `[0, 1]` uses time t and t - step
+    pub lags: Vec<usize>,
+    /// Steps ahead of the target
+    pub horizon: usize,
+    /// Observations between two consecutive samples
+    pub stride: usize,
+}
+
+impl Windowing {
+    /// Inputs at t only, every observation is a sample
+    pub fn new(step: Duration, horizon: usize) -> Windowing {
+        Windowing {
+            step,
+            lags: vec![0],
+            horizon,
+            stride: 1,
+        }
+    }
+}
+
+impl TimeSeries {
+    pub fn new(
+        times: Vec<DateTime<Utc>>,
+        inputs: Vec<Vec<f64>>,
+        targets: Vec<Vec<f64>>,
+    ) -> TimeSeries {
+        if times.len() != inputs.len() || times.len() != targets.len() {
+            panic!("times, inputs and targets must have the same size");
+        }
+        if times.windows(2).any(|t| t[0] >= t[1]) {
+            panic!("times must be strictly increasing");
+        }
+        TimeSeries {
+            times,
+            inputs,
+            targets,
+        }
+    }
+
+    /// Samples in time order with the inputs of every lag concatenated and the target
+    /// `horizon` steps ahead, and the time t of each sample. Missing targets are skipped.
+    pub fn windows(&self, w: &Windowing) -> (DataSet, Vec<DateTime<Utc>>) {
+        if w.stride == 0 {
+            panic!("stride must be greater than 0");
+        }
+        let at: HashMap<DateTime<Utc>, usize> = self
+            .times
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (*t, i))
+            .collect();
+
+        let mut datas: Vec<Data> = vec![];
+        let mut times: Vec<DateTime<Utc>> = vec![];
+        for (i, t) in self.times.iter().enumerate().step_by(w.stride) {
+            let target = match at.get(&(*t + w.step * w.horizon as i32)) {
+                Some(&j) if !self.targets[j].iter().any(|v| v.is_nan()) => j,
+                _ => continue,
+            };
+            let mut inputs: Vec<f64> = vec![];
+            let mut complete = true;
+            for lag in w.lags.iter() {
+                match at.get(&(*t - w.step * *lag as i32)) {
+                    Some(&j) => inputs.extend_from_slice(&self.inputs[j]),
+                    None => {
+                        complete = false;
+                        break;
+                    }
+                }
+            }
+            if complete {
+                datas.push(Data {
+                    inputs,
+                    labels: self.targets[target].clone(),
+                });
+                times.push(self.times[i]);
+            }
+        }
+        (DataSet::new(datas), times)
+    }
+}
+
+/// Expanding window: the set (in time order) is cut into `k + 1` blocks, fold `i` trains
+/// on blocks `0..=i` and validates on block `i + 1`. `gap` samples before each
+/// validation block are left out, use the horizon so no training target overlaps it.
+pub struct ForwardChaining {
+    pub k: usize,
+    pub gap: usize,
+}
+
+impl ForwardChaining {
+    pub fn new(k: usize) -> ForwardChaining {
+        ForwardChaining { k, gap: 0 }
+    }
+}
+
+impl Splitter for ForwardChaining {
+    fn split(&self, dataset: &DataSet) -> Vec<Fold> {
+        let n = dataset.len();
+        let block = n / (self.k + 1);
+        if self.k == 0 || block <= self.gap {
+            panic!("not enough samples for k blocks larger than gap");
+        }
+        (1..=self.k)
+            .map(|i| {
+                let end = if i == self.k { n } else { (i + 1) * block };
+                Fold {
+                    train: (0..i * block - self.gap).collect(),
+                    valid: (i * block..end).collect(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Fixed size training window of `train` samples followed by `valid` samples, moved
+/// forward by `valid` samples each fold
+pub struct SlidingWindow {
+    pub train: usize,
+    pub valid: usize,
+    pub gap: usize,
+}
+
+impl SlidingWindow {
+    pub fn new(train: usize, valid: usize) -> SlidingWindow {
+        SlidingWindow {
+            train,
+            valid,
+            gap: 0,
+        }
+    }
+}
+
+impl Splitter for SlidingWindow {
+    fn split(&self, dataset: &DataSet) -> Vec<Fold> {
+        if self.train == 0 || self.valid == 0 {
+            panic!("train and valid must be greater than 0");
+        }
+        let mut folds: Vec<Fold> = vec![];
+        let mut start = 0;
+        while start + self.train + self.gap + self.valid <= dataset.len() {
+            let valid_start = start + self.train + self.gap;
+            folds.push(Fold {
+                train: (start..start + self.train).collect(),
+                valid: (valid_start..valid_start + self.valid).collect(),
+            });
+            start += self.valid;
+        }
+        folds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// Hourly series of value i at hour i, hours 3 and 4 are missing
+    fn series() -> TimeSeries {
+        let hours: Vec<i64> = (0..12).filter(|h| *h != 3 && *h != 4).collect();
+        let start = Utc.ymd(2004, 3, 10).and_hms(0, 0, 0);
+        TimeSeries::new(
+            hours.iter().map(|h| start + Duration::hours(*h)).collect(),
+            hours.iter().map(|h| vec![*h as f64]).collect(),
+            hours.iter().map(|h| vec![*h as f64 * 10.0]).collect(),
+        )
+    }
+
+    #[test]
+    fn test_windows() {
+        let s = series();
+        let mut w = Windowing::new(Duration::hours(1), 2);
+        w.lags = vec![0, 1];
+        let (dt, times) = s.windows(&w);
+        let inputs: Vec<Vec<f64>> = dt.get_datas().iter().map(|d| d.inputs.clone()).collect();
+        // anchors 0 (no t-1), 1 and 2 (no target), 5 (no t-1) and 10, 11 are skipped
+        assert_eq!(
+            inputs,
+            vec![
+                vec![6.0, 5.0],
+                vec![7.0, 6.0],
+                vec![8.0, 7.0],
+                vec![9.0, 8.0]
+            ]
+        );
+        assert_eq!(dt.get_label(0), vec![80.0, 90.0, 100.0, 110.0]);
+        assert_eq!(times.len(), 4);
+
+        w.stride = 2;
+        let (dt, _) = s.windows(&w);
+        assert_eq!(dt.get_feature(0), vec![6.0, 8.0]);
+    }
+
+    #[test]
+    fn test_forward_chaining() {
+        let dt = DataSet::new(
+            (0..10)
+                .map(|i| Data {
+                    inputs: vec![i as f64],
+                    labels: vec![0.0],
+                })
+                .collect(),
+        );
+        let mut fc = ForwardChaining::new(4);
+        fc.gap = 1;
+        let folds = fc.split(&dt);
+        assert_eq!(folds.len(), 4);
+        assert_eq!(folds[0].train, vec![0]);
+        assert_eq!(folds[0].valid, vec![2, 3]);
+        assert_eq!(folds[3].valid, vec![8, 9]);
+        for f in folds.iter() {
+            assert!(f.train.iter().max() < f.valid.iter().min());
+        }
+
+        let folds = SlidingWindow::new(4, 2).split(&dt);
+        assert_eq!(folds.len(), 3);
+        assert_eq!(folds[1].train, vec![2, 3, 4, 5]);
+        assert_eq!(folds[1].valid, vec![6, 7]);
+    }
+}